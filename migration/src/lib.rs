@@ -9,6 +9,24 @@ mod m20240524_200614_create_chat_admins_table;
 mod m20240531_121814_modify_messages_table;
 mod m20240531_143248_insert_data_to_messages;
 mod m20240602_073326_add_foreign_keys;
+mod m20261018_120000_add_hash_algorithms_to_memes;
+mod m20261018_130000_convert_hashes_to_binary;
+mod m20261018_140000_add_file_unique_id_to_memes;
+mod m20261018_141000_create_meme_hashes_table;
+mod m20261018_150000_add_album_msg_ids_to_memes;
+mod m20261018_160000_add_media_kind_to_memes;
+mod m20261018_170000_add_thresholds_to_chats;
+mod m20261018_180000_add_schedule_to_chats;
+mod m20261018_190000_create_scheduled_runs_table;
+mod m20261018_200000_add_recap_to_users;
+mod m20261018_210000_add_caption_to_memes;
+mod m20261018_220000_add_language_to_chats;
+mod m20261018_221000_add_language_to_messages;
+mod m20261018_230000_add_enabled_to_messages;
+mod m20261018_231000_add_weight_to_messages;
+mod m20261018_232000_create_meme_complaints_table;
+mod m20261018_233000_add_reposted_at_to_memes;
+mod m20261018_234000_create_recap_runs_table;
 
 pub struct Migrator;
 
@@ -25,6 +43,24 @@ impl MigratorTrait for Migrator {
             Box::new(m20240531_121814_modify_messages_table::Migration),
             Box::new(m20240531_143248_insert_data_to_messages::Migration),
             Box::new(m20240602_073326_add_foreign_keys::Migration),
+            Box::new(m20261018_120000_add_hash_algorithms_to_memes::Migration),
+            Box::new(m20261018_130000_convert_hashes_to_binary::Migration),
+            Box::new(m20261018_140000_add_file_unique_id_to_memes::Migration),
+            Box::new(m20261018_141000_create_meme_hashes_table::Migration),
+            Box::new(m20261018_150000_add_album_msg_ids_to_memes::Migration),
+            Box::new(m20261018_160000_add_media_kind_to_memes::Migration),
+            Box::new(m20261018_170000_add_thresholds_to_chats::Migration),
+            Box::new(m20261018_180000_add_schedule_to_chats::Migration),
+            Box::new(m20261018_190000_create_scheduled_runs_table::Migration),
+            Box::new(m20261018_200000_add_recap_to_users::Migration),
+            Box::new(m20261018_210000_add_caption_to_memes::Migration),
+            Box::new(m20261018_220000_add_language_to_chats::Migration),
+            Box::new(m20261018_221000_add_language_to_messages::Migration),
+            Box::new(m20261018_230000_add_enabled_to_messages::Migration),
+            Box::new(m20261018_231000_add_weight_to_messages::Migration),
+            Box::new(m20261018_232000_create_meme_complaints_table::Migration),
+            Box::new(m20261018_233000_add_reposted_at_to_memes::Migration),
+            Box::new(m20261018_234000_create_recap_runs_table::Migration),
         ]
     }
 }
@@ -0,0 +1,38 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Memes::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Memes::PerceptualHash).string_len(64).null())
+                    .add_column_if_not_exists(ColumnDef::new(Memes::DifferenceHash).string_len(64).null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Memes::Table)
+                    .drop_column(Memes::PerceptualHash)
+                    .drop_column(Memes::DifferenceHash)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Memes {
+    Table,
+    PerceptualHash,
+    DifferenceHash,
+}
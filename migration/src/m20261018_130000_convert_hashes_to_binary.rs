@@ -0,0 +1,38 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Hex strings of bits become raw bytes and big-endian integers
+        db.execute_unprepared(
+            r#"ALTER TABLE "memes"
+                ALTER COLUMN "long_hash" TYPE bytea USING decode("long_hash", 'hex'),
+                ALTER COLUMN "short_hash" TYPE bigint USING ('x' || lpad("short_hash", 16, '0'))::bit(64)::bigint,
+                ALTER COLUMN "perceptual_hash" TYPE bigint USING ('x' || lpad("perceptual_hash", 16, '0'))::bit(64)::bigint,
+                ALTER COLUMN "difference_hash" TYPE bigint USING ('x' || lpad("difference_hash", 16, '0'))::bit(64)::bigint"#,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            r#"ALTER TABLE "memes"
+                ALTER COLUMN "long_hash" TYPE varchar(256) USING upper(encode("long_hash", 'hex')),
+                ALTER COLUMN "short_hash" TYPE varchar(4) USING upper(lpad(to_hex("short_hash"), 4, '0')),
+                ALTER COLUMN "perceptual_hash" TYPE varchar(64) USING upper(lpad(to_hex("perceptual_hash"), 16, '0')),
+                ALTER COLUMN "difference_hash" TYPE varchar(64) USING upper(lpad(to_hex("difference_hash"), 16, '0'))"#,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
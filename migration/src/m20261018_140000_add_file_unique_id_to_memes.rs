@@ -0,0 +1,65 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Memes::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Memes::FileUniqueId).string_len(128).null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"UPDATE "memes" SET "file_unique_id" = CASE
+                    WHEN jsonb_typeof("photos") = 'array' THEN "photos"->-1->>'file_unique_id'
+                    ELSE "photos"->>'file_unique_id'
+                END"#,
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("memes_chat_id_file_unique_id_idx")
+                    .table(Memes::Table)
+                    .col(Memes::ChatId)
+                    .col(Memes::FileUniqueId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .table(Memes::Table)
+                    .if_exists()
+                    .name("memes_chat_id_file_unique_id_idx")
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Memes::Table)
+                    .drop_column(Memes::FileUniqueId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Memes {
+    Table,
+    ChatId,
+    FileUniqueId,
+}
@@ -0,0 +1,87 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MemeHashes::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MemeHashes::Uuid)
+                            .uuid()
+                            .not_null()
+                            .default(Expr::cust("gen_random_uuid()"))
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(MemeHashes::MemeUuid).uuid().not_null())
+                    .col(ColumnDef::new(MemeHashes::Position).small_integer().not_null())
+                    .col(ColumnDef::new(MemeHashes::LongHash).binary().null())
+                    .col(ColumnDef::new(MemeHashes::ShortHash).big_integer().null())
+                    .col(ColumnDef::new(MemeHashes::PerceptualHash).big_integer().null())
+                    .col(ColumnDef::new(MemeHashes::DifferenceHash).big_integer().null())
+                    .col(
+                        ColumnDef::new(MemeHashes::CreatedAt)
+                            .timestamp()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("meme_hashes_meme_uuid_fkey")
+                            .from(MemeHashes::Table, MemeHashes::MemeUuid)
+                            .to(Memes::Table, Memes::Uuid)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("meme_hashes_meme_uuid_idx")
+                    .table(MemeHashes::Table)
+                    .col(MemeHashes::MemeUuid)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .table(MemeHashes::Table)
+                    .if_exists()
+                    .name("meme_hashes_meme_uuid_idx")
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(MemeHashes::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MemeHashes {
+    Table,
+    Uuid,
+    MemeUuid,
+    Position,
+    LongHash,
+    ShortHash,
+    PerceptualHash,
+    DifferenceHash,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Memes {
+    Table,
+    Uuid,
+}
@@ -0,0 +1,76 @@
+use sea_orm_migration::prelude::extension::postgres::Type;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(Alias::new("MediaKind"))
+                    .values([
+                        MediaKinds::Photo,
+                        MediaKinds::Video,
+                        MediaKinds::Album,
+                        MediaKinds::Animation,
+                        MediaKinds::Sticker,
+                    ])
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Memes::Table)
+                    .add_column(
+                        ColumnDef::new(Memes::MediaKind)
+                            .custom(Alias::new("\"MediaKind\""))
+                            .not_null()
+                            .default(Expr::cust("'photo'")),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"UPDATE "memes" SET "media_kind" = 'video' WHERE jsonb_typeof("photos") = 'object';
+                UPDATE "memes" SET "media_kind" = 'album' WHERE jsonb_typeof("photos"->0) = 'array'"#,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Memes::Table)
+                    .drop_column(Memes::MediaKind)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_type(Type::drop().name(Alias::new("MediaKind")).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MediaKinds {
+    Photo,
+    Video,
+    Album,
+    Animation,
+    Sticker,
+}
+
+#[derive(DeriveIden)]
+enum Memes {
+    Table,
+    MediaKind,
+}
@@ -0,0 +1,38 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Chats::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Chats::SimilarThreshold).small_integer().null())
+                    .add_column_if_not_exists(ColumnDef::new(Chats::DuplicateThreshold).small_integer().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Chats::Table)
+                    .drop_column(Chats::SimilarThreshold)
+                    .drop_column(Chats::DuplicateThreshold)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Chats {
+    Table,
+    SimilarThreshold,
+    DuplicateThreshold,
+}
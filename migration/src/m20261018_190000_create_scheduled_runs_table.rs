@@ -0,0 +1,67 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ScheduledRuns::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ScheduledRuns::Uuid)
+                            .uuid()
+                            .not_null()
+                            .default(Expr::cust("gen_random_uuid()"))
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ScheduledRuns::ChatId).big_integer().not_null())
+                    .col(ColumnDef::new(ScheduledRuns::Period).string_len(16).not_null())
+                    .col(ColumnDef::new(ScheduledRuns::WindowFrom).timestamp().not_null())
+                    .col(ColumnDef::new(ScheduledRuns::WindowTo).timestamp().not_null())
+                    .col(ColumnDef::new(ScheduledRuns::FinishedAt).timestamp().null())
+                    .col(
+                        ColumnDef::new(ScheduledRuns::CreatedAt)
+                            .timestamp()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .unique()
+                    .name("scheduled_runs_chat_id_period_window_idx")
+                    .table(ScheduledRuns::Table)
+                    .col(ScheduledRuns::ChatId)
+                    .col(ScheduledRuns::Period)
+                    .col(ScheduledRuns::WindowFrom)
+                    .col(ScheduledRuns::WindowTo)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ScheduledRuns::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ScheduledRuns {
+    Table,
+    Uuid,
+    ChatId,
+    Period,
+    WindowFrom,
+    WindowTo,
+    FinishedAt,
+    CreatedAt,
+}
@@ -0,0 +1,72 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MemeComplaints::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MemeComplaints::Uuid)
+                            .uuid()
+                            .not_null()
+                            .default(Expr::cust("gen_random_uuid()"))
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(MemeComplaints::MemeUuid).uuid().not_null())
+                    .col(ColumnDef::new(MemeComplaints::UserId).big_integer().not_null())
+                    .col(
+                        ColumnDef::new(MemeComplaints::CreatedAt)
+                            .timestamp()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("meme_complaints_meme_uuid_fkey")
+                            .from(MemeComplaints::Table, MemeComplaints::MemeUuid)
+                            .to(Memes::Table, Memes::Uuid)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .unique()
+                    .name("meme_complaints_meme_uuid_user_id_idx")
+                    .table(MemeComplaints::Table)
+                    .col(MemeComplaints::MemeUuid)
+                    .col(MemeComplaints::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MemeComplaints::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MemeComplaints {
+    Table,
+    Uuid,
+    MemeUuid,
+    UserId,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Memes {
+    Table,
+    Uuid,
+}
@@ -0,0 +1,74 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RecapRuns::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RecapRuns::Uuid)
+                            .uuid()
+                            .not_null()
+                            .default(Expr::cust("gen_random_uuid()"))
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(RecapRuns::UserId).big_integer().not_null())
+                    .col(ColumnDef::new(RecapRuns::Year).integer().not_null())
+                    .col(ColumnDef::new(RecapRuns::FinishedAt).timestamp().null())
+                    .col(
+                        ColumnDef::new(RecapRuns::CreatedAt)
+                            .timestamp()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .unique()
+                    .name("recap_runs_user_id_year_idx")
+                    .table(RecapRuns::Table)
+                    .col(RecapRuns::UserId)
+                    .col(RecapRuns::Year)
+                    .to_owned(),
+            )
+            .await?;
+        // Recaps were claimed in the ledger of chats with user ids as chat ids
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"INSERT INTO "recap_runs" ("user_id", "year", "finished_at", "created_at")
+                    SELECT "chat_id", EXTRACT(YEAR FROM "window_to")::integer, "finished_at", "created_at"
+                    FROM "scheduled_runs" WHERE "period" = 'recap'
+                    ON CONFLICT DO NOTHING;
+                DELETE FROM "scheduled_runs" WHERE "period" = 'recap'"#,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RecapRuns::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RecapRuns {
+    Table,
+    Uuid,
+    UserId,
+    Year,
+    FinishedAt,
+    CreatedAt,
+}
@@ -0,0 +1,217 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use envconfig::Envconfig;
+use once_cell::sync::OnceCell;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+pub static INSTANCE: OnceCell<Calendar> = OnceCell::new();
+
+#[derive(Envconfig, Clone, Debug)]
+pub struct CalendarConfig {
+    /// Chats without their own timezone get statistics by this one
+    #[envconfig(from = "TIMEZONE", default = "UTC")]
+    pub timezone: Tz,
+    /// Periods end at this local time of their last working day
+    #[envconfig(from = "PERIOD_CUTOFF", default = "16:00:00")]
+    pub cutoff: NaiveTime,
+    #[envconfig(from = "WEEKEND", default = "Sat,Sun")]
+    pub weekend: Weekend,
+    /// Dates like 2026-01-01 per line are holidays, ones like +2026-11-01 are working weekend days
+    #[envconfig(from = "HOLIDAYS_FILE")]
+    pub holidays_file: Option<String>,
+}
+
+/// Weekdays separated by `,`, like `Sat,Sun`
+#[derive(Clone, Debug)]
+pub struct Weekend(Vec<Weekday>);
+
+impl FromStr for Weekend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|day| !day.is_empty())
+            .map(|day| Weekday::from_str(day).map_err(|_| format!("Unknown weekday: {day}")))
+            .collect::<Result<Vec<Weekday>, String>>()
+            .map(Weekend)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Calendar {
+    timezone: Tz,
+    cutoff: NaiveTime,
+    weekend: Vec<Weekday>,
+    holidays: HashSet<NaiveDate>,
+    working_days: HashSet<NaiveDate>,
+}
+
+impl Calendar {
+    pub fn new(config: &CalendarConfig) -> Result<Self> {
+        let mut calendar = Self {
+            timezone: config.timezone,
+            cutoff: config.cutoff,
+            weekend: config.weekend.0.clone(),
+            holidays: HashSet::new(),
+            working_days: HashSet::new(),
+        };
+
+        if let Some(path) = config.holidays_file.as_deref().filter(|path| !path.is_empty()) {
+            calendar.load_holidays(&std::fs::read_to_string(path)?)?;
+            info!("Loaded {} holidays from {path}", calendar.holidays.len());
+        }
+
+        Ok(calendar)
+    }
+
+    pub fn global() -> &'static Calendar {
+        INSTANCE.get().expect("Calendar is not initialized")
+    }
+
+    /// The same working days in another timezone
+    pub fn with_timezone(&self, timezone: Tz) -> Self {
+        Self {
+            timezone,
+            ..self.clone()
+        }
+    }
+
+    pub fn timezone(&self) -> Tz {
+        self.timezone
+    }
+
+    /// Empty lines and ones starting with `#` are skipped
+    fn load_holidays(&mut self, text: &str) -> Result<()> {
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (days, date) = match line.strip_prefix('+') {
+                Some(date) => (&mut self.working_days, date),
+                None => (&mut self.holidays, line),
+            };
+            let date = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+                .map_err(|e| anyhow!("Wrong holiday \"{line}\": {e}"))?;
+
+            days.insert(date);
+        }
+
+        Ok(())
+    }
+
+    /// Local date of the moment
+    pub fn date(&self, at: DateTime<Utc>) -> NaiveDate {
+        at.with_timezone(&self.timezone).date_naive()
+    }
+
+    pub fn is_working_day(&self, date: NaiveDate) -> bool {
+        if self.working_days.contains(&date) {
+            return true;
+        }
+
+        !self.weekend.contains(&date.weekday()) && !self.holidays.contains(&date)
+    }
+
+    /// The last working day between dates, `last` if there are none
+    pub fn last_working_day(&self, first: NaiveDate, last: NaiveDate) -> NaiveDate {
+        std::iter::successors(Some(last), NaiveDate::pred_opt)
+            .take_while(|date| *date >= first)
+            .find(|date| self.is_working_day(*date))
+            .unwrap_or(last)
+    }
+
+    /// The cutoff time of the date
+    pub fn cutoff(&self, date: NaiveDate) -> DateTime<Utc> {
+        let local = date.and_time(self.cutoff);
+
+        self.timezone
+            .from_local_datetime(&local)
+            .earliest()
+            // The cutoff is skipped by a DST transition
+            .or_else(|| {
+                self.timezone
+                    .from_local_datetime(&(local + Duration::hours(1)))
+                    .earliest()
+            })
+            .map(|cutoff| cutoff.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(&local))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::utils::Period;
+
+    fn calendar(holidays: &str) -> Calendar {
+        let mut calendar = Calendar::new(&CalendarConfig {
+            timezone: Tz::UTC,
+            cutoff: NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+            weekend: Weekend(vec![Weekday::Sat, Weekday::Sun]),
+            holidays_file: None,
+        })
+        .unwrap();
+
+        calendar.load_holidays(holidays).unwrap();
+        calendar
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn weekend_moves_the_end_of_month_to_friday() {
+        // 31 January 2026 is Saturday
+        let calendar = calendar("");
+
+        assert!(Period::Month.is_due(&calendar, at("2026-01-30T17:00:00Z")));
+        assert!(!Period::Month.is_due(&calendar, at("2026-01-31T17:00:00Z")));
+    }
+
+    #[test]
+    fn holiday_moves_the_end_of_month_to_the_day_before() {
+        let calendar = calendar("# Some holiday\n2026-01-30\n");
+
+        assert!(!calendar.is_working_day(NaiveDate::from_ymd_opt(2026, 1, 30).unwrap()));
+        assert!(Period::Month.is_due(&calendar, at("2026-01-29T17:00:00Z")));
+        assert!(!Period::Month.is_due(&calendar, at("2026-01-30T17:00:00Z")));
+    }
+
+    #[test]
+    fn holiday_friday_ends_the_week_on_thursday() {
+        // 30 January 2026 is Friday
+        let calendar = calendar("2026-01-30");
+
+        assert!(Period::Week.is_due(&calendar, at("2026-01-29T17:00:00Z")));
+        assert!(!Period::Week.is_due(&calendar, at("2026-01-30T17:00:00Z")));
+        assert_eq!(
+            Period::Week.last_dates(&calendar, at("2026-01-30T17:00:00Z")).1 + Duration::nanoseconds(1),
+            at("2026-01-29T16:00:00Z")
+        );
+    }
+
+    #[test]
+    fn working_saturday_ends_the_month() {
+        let calendar = calendar("+2026-01-31");
+
+        assert!(calendar.is_working_day(NaiveDate::from_ymd_opt(2026, 1, 31).unwrap()));
+        assert!(!Period::Month.is_due(&calendar, at("2026-01-30T17:00:00Z")));
+        assert!(Period::Month.is_due(&calendar, at("2026-01-31T17:00:00Z")));
+        assert_eq!(
+            Period::Month.last_dates(&calendar, at("2026-02-02T10:00:00Z")).1 + Duration::nanoseconds(1),
+            at("2026-01-31T16:00:00Z")
+        );
+    }
+
+    #[test]
+    fn wrong_holiday_is_an_error() {
+        let mut calendar = calendar("");
+
+        assert!(calendar.load_holidays("2026-02-30").is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::Result;
+
+opencv::opencv_branch_4! {
+    use opencv::{
+        core::{self, Mat, Point, Rect, Scalar},
+        imgcodecs, imgproc,
+    };
+}
+
+const WIDTH: i32 = 900;
+const PANEL_HEIGHT: i32 = 320;
+const MARGIN: i32 = 40;
+/// Memes with more likes or dislikes are put to the last bucket of the histogram
+const HISTOGRAM_BUCKETS: usize = 11;
+
+/// Colors are BGR
+const BLACK: (f64, f64, f64) = (0., 0., 0.);
+const GRAY: (f64, f64, f64) = (200., 200., 200.);
+const BLUE: (f64, f64, f64) = (200., 120., 40.);
+const GREEN: (f64, f64, f64) = (80., 170., 60.);
+const RED: (f64, f64, f64) = (60., 60., 210.);
+const ORANGE: (f64, f64, f64) = (40., 150., 240.);
+
+/// Bars of one or more series, drawn side by side in each group
+struct Panel<'a> {
+    title: &'a str,
+    labels: Vec<String>,
+    series: Vec<(Vec<i64>, (f64, f64, f64))>,
+}
+
+/// Report image of a period. Hershey fonts of OpenCV have no cyrillic, so texts must be ASCII.
+#[derive(Default)]
+pub struct ReportChart {
+    pub days: Vec<(String, i64)>,
+    pub likes: Vec<i64>,
+    pub dislikes: Vec<i64>,
+    pub senders: Vec<(String, i64)>,
+}
+
+impl ReportChart {
+    /// Puts a count of likes or dislikes to its bucket
+    pub fn bucket(histogram: &mut Vec<i64>, count: i64) {
+        histogram.resize(HISTOGRAM_BUCKETS, 0);
+        histogram[(count.max(0) as usize).min(HISTOGRAM_BUCKETS - 1)] += 1;
+    }
+
+    /// PNG image with memes per day, histogram of likes and dislikes and top senders
+    pub fn render(&self) -> Result<Vec<u8>> {
+        let buckets = (0..HISTOGRAM_BUCKETS)
+            .map(|i| match i {
+                i if i == HISTOGRAM_BUCKETS - 1 => format!("{i}+"),
+                i => i.to_string(),
+            })
+            .collect();
+        let panels = [
+            Panel {
+                title: "Memes per day",
+                labels: self.days.iter().map(|(day, _)| day.clone()).collect(),
+                series: vec![(self.days.iter().map(|(_, count)| *count).collect(), BLUE)],
+            },
+            Panel {
+                title: "Memes by likes (green) and dislikes (red)",
+                labels: buckets,
+                series: vec![(self.likes.clone(), GREEN), (self.dislikes.clone(), RED)],
+            },
+            Panel {
+                title: "Top memesenders",
+                labels: self.senders.iter().map(|(name, _)| name.clone()).collect(),
+                series: vec![(self.senders.iter().map(|(_, count)| *count).collect(), ORANGE)],
+            },
+        ];
+
+        let mut image = Mat::new_rows_cols_with_default(
+            PANEL_HEIGHT * panels.len() as i32,
+            WIDTH,
+            core::CV_8UC3,
+            Scalar::all(255.),
+        )?;
+
+        for (i, panel) in panels.iter().enumerate() {
+            Self::draw_panel(&mut image, panel, PANEL_HEIGHT * i as i32)?;
+        }
+
+        let mut buf = core::Vector::<u8>::new();
+        imgcodecs::imencode(".png", &image, &mut buf, &core::Vector::new())?;
+
+        Ok(buf.to_vec())
+    }
+
+    fn draw_panel(image: &mut Mat, panel: &Panel, top: i32) -> Result<()> {
+        let bottom = top + PANEL_HEIGHT - MARGIN;
+        let height = PANEL_HEIGHT - MARGIN * 3;
+        let max = panel
+            .series
+            .iter()
+            .flat_map(|(values, _)| values.iter().copied())
+            .max()
+            .unwrap_or_default()
+            .max(1);
+
+        Self::text(image, panel.title, Point::new(MARGIN, top + MARGIN), 0.7, 2)?;
+        imgproc::line(
+            image,
+            Point::new(MARGIN, bottom),
+            Point::new(WIDTH - MARGIN, bottom),
+            color(GRAY),
+            1,
+            imgproc::LINE_8,
+            0,
+        )?;
+
+        if panel.labels.is_empty() {
+            return Ok(());
+        }
+
+        let group = (WIDTH - MARGIN * 2) / panel.labels.len() as i32;
+        let bar = (group * 3 / 4 / panel.series.len() as i32).max(1);
+        // Labels of many bars overlap, so only some of them are shown
+        let label_step = (panel.labels.len() / 16).max(1);
+
+        for (i, label) in panel.labels.iter().enumerate() {
+            let x = MARGIN + group * i as i32 + group / 8;
+
+            for (s, (values, bar_color)) in panel.series.iter().enumerate() {
+                let value = values.get(i).copied().unwrap_or_default();
+                let bar_height = (value * height as i64 / max) as i32;
+                let bar_x = x + bar * s as i32;
+
+                if value > 0 {
+                    imgproc::rectangle(
+                        image,
+                        Rect::new(bar_x, bottom - bar_height, bar, bar_height),
+                        color(*bar_color),
+                        imgproc::FILLED,
+                        imgproc::LINE_8,
+                        0,
+                    )?;
+                    Self::text(
+                        image,
+                        &value.to_string(),
+                        Point::new(bar_x, bottom - bar_height - 6),
+                        0.4,
+                        1,
+                    )?;
+                }
+            }
+
+            if i % label_step == 0 {
+                Self::text(image, label, Point::new(x, bottom + 20), 0.45, 1)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn text(image: &mut Mat, text: &str, origin: Point, scale: f64, thickness: i32) -> Result<()> {
+        imgproc::put_text(
+            image,
+            text,
+            origin,
+            imgproc::FONT_HERSHEY_SIMPLEX,
+            scale,
+            color(BLACK),
+            thickness,
+            imgproc::LINE_AA,
+            false,
+        )?;
+
+        Ok(())
+    }
+}
+
+fn color((b, g, r): (f64, f64, f64)) -> Scalar {
+    Scalar::new(b, g, r, 0.)
+}
@@ -0,0 +1,109 @@
+use anyhow::{anyhow, Result};
+
+opencv::opencv_branch_4! {
+    use opencv::{
+        core::{self, Mat, Point, Rect, Scalar, Size},
+        imgcodecs, imgproc,
+        prelude::*,
+    };
+}
+
+const TILE: i32 = 360;
+const COLUMNS: usize = 3;
+const LABEL_HEIGHT: i32 = 40;
+
+/// Downloaded image of a meme with its place
+pub struct Tile {
+    pub path: String,
+    pub rank: usize,
+    pub likes: i64,
+}
+
+/// PNG image of tiles, in rows of three. Tiles which can't be read are left empty.
+pub fn render(tiles: &[Tile]) -> Result<Vec<u8>> {
+    if tiles.is_empty() {
+        return Err(anyhow!("No tiles for collage"));
+    }
+
+    let columns = tiles.len().min(COLUMNS);
+    let rows = tiles.len().div_ceil(COLUMNS);
+    let mut collage = Mat::new_rows_cols_with_default(
+        TILE * rows as i32,
+        TILE * columns as i32,
+        core::CV_8UC3,
+        Scalar::all(30.),
+    )?;
+
+    for (i, tile) in tiles.iter().enumerate() {
+        let area = Rect::new(TILE * (i % COLUMNS) as i32, TILE * (i / COLUMNS) as i32, TILE, TILE);
+
+        if let Err(e) = draw_image(&mut collage, &tile.path, area) {
+            warn!("Can't draw {} in collage: {e}", tile.path);
+        }
+
+        draw_label(&mut collage, &format!("#{}  {} likes", tile.rank, tile.likes), area)?;
+    }
+
+    let mut buf = core::Vector::<u8>::new();
+    imgcodecs::imencode(".png", &collage, &mut buf, &core::Vector::new())?;
+
+    Ok(buf.to_vec())
+}
+
+/// Fits the image into the area keeping its proportions
+fn draw_image(collage: &mut Mat, path: &str, area: Rect) -> Result<()> {
+    let image = imgcodecs::imread(path, imgcodecs::IMREAD_COLOR)?;
+
+    if image.empty() {
+        return Err(anyhow!("Image is empty"));
+    }
+
+    let size = image.size()?;
+    let scale = f64::min(
+        area.width as f64 / size.width as f64,
+        area.height as f64 / size.height as f64,
+    );
+    let fitted = Size::new(
+        ((size.width as f64 * scale) as i32).clamp(1, area.width),
+        ((size.height as f64 * scale) as i32).clamp(1, area.height),
+    );
+    let mut resized = Mat::default();
+    imgproc::resize(&image, &mut resized, fitted, 0., 0., imgproc::INTER_AREA)?;
+
+    let mut roi = Mat::roi_mut(
+        collage,
+        Rect::new(
+            area.x + (area.width - fitted.width) / 2,
+            area.y + (area.height - fitted.height) / 2,
+            fitted.width,
+            fitted.height,
+        ),
+    )?;
+    resized.copy_to(&mut roi)?;
+
+    Ok(())
+}
+
+fn draw_label(collage: &mut Mat, text: &str, area: Rect) -> Result<()> {
+    imgproc::rectangle(
+        collage,
+        Rect::new(area.x, area.y + area.height - LABEL_HEIGHT, area.width, LABEL_HEIGHT),
+        Scalar::all(0.),
+        imgproc::FILLED,
+        imgproc::LINE_8,
+        0,
+    )?;
+    imgproc::put_text(
+        collage,
+        text,
+        Point::new(area.x + 12, area.y + area.height - 12),
+        imgproc::FONT_HERSHEY_SIMPLEX,
+        0.8,
+        Scalar::all(255.),
+        2,
+        imgproc::LINE_AA,
+        false,
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,211 @@
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use futures::{Stream, TryStreamExt};
+use sea_orm::DbErr;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+use teloxide::types::{Animation, PhotoSize, Sticker, Video};
+use uuid::Uuid;
+
+use super::Application;
+use crate::bot::types::AlbumItem;
+use crate::database::entity::{
+    memes::{MediaKind, MemeExport},
+    prelude::*,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    /// JSON object per line
+    Jsonl,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" | "json" => Ok(ExportFormat::Jsonl),
+            _ => Err(format!("Unknown export format: {s}")),
+        }
+    }
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match *self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+/// Rows written to each file
+#[derive(Debug, Default)]
+pub struct ExportStats {
+    pub memes: u64,
+    pub users: u64,
+    pub votes: u64,
+}
+
+#[derive(Serialize)]
+struct MemeRecord {
+    uuid: Uuid,
+    msg_id: Option<i64>,
+    user_id: i64,
+    username: Option<String>,
+    posted_at: Option<NaiveDateTime>,
+    media_kind: &'static str,
+    caption: Option<String>,
+    /// Separated by spaces, one per photo of an album
+    file_ids: String,
+    likes: i64,
+    dislikes: i64,
+}
+
+impl From<MemeExport> for MemeRecord {
+    fn from(meme: MemeExport) -> Self {
+        Self {
+            file_ids: file_ids(meme.media_kind, meme.photos).join(" "),
+            uuid: meme.uuid,
+            msg_id: meme.msg_id,
+            user_id: meme.user_id,
+            username: meme.username,
+            posted_at: meme.posted_at,
+            media_kind: match meme.media_kind {
+                MediaKind::Photo => "photo",
+                MediaKind::Video => "video",
+                MediaKind::Album => "album",
+                MediaKind::Animation => "animation",
+                MediaKind::Sticker => "sticker",
+            },
+            caption: meme.caption,
+            likes: meme.likes,
+            dislikes: meme.dislikes,
+        }
+    }
+}
+
+/// Biggest photos and files of the media
+fn file_ids(kind: MediaKind, media: Option<serde_json::Value>) -> Vec<String> {
+    let Some(media) = media else {
+        return Vec::new();
+    };
+
+    let ids = match kind {
+        MediaKind::Photo => serde_json::from_value::<Vec<PhotoSize>>(media)
+            .ok()
+            .and_then(|mut photos| photos.pop())
+            .map(|p| vec![p.file.id]),
+        MediaKind::Album => serde_json::from_value::<Vec<AlbumItem>>(media)
+            .ok()
+            .map(|album| album.iter().filter_map(AlbumItem::file_id).collect()),
+        MediaKind::Video => serde_json::from_value::<Video>(media).ok().map(|v| vec![v.file.id]),
+        MediaKind::Animation => serde_json::from_value::<Animation>(media).ok().map(|a| vec![a.file.id]),
+        MediaKind::Sticker => serde_json::from_value::<Sticker>(media).ok().map(|s| vec![s.file.id]),
+    };
+
+    ids.unwrap_or_default()
+}
+
+enum RowWriter {
+    Csv(Box<csv::Writer<File>>),
+    Jsonl(BufWriter<File>),
+}
+
+impl RowWriter {
+    fn create(dir: &Path, name: &str, format: ExportFormat) -> Result<Self> {
+        let file = File::create(dir.join(format!("{name}.{}", format.extension())))?;
+
+        Ok(match format {
+            ExportFormat::Csv => RowWriter::Csv(Box::new(csv::Writer::from_writer(file))),
+            ExportFormat::Jsonl => RowWriter::Jsonl(BufWriter::new(file)),
+        })
+    }
+
+    fn write<T: Serialize>(&mut self, row: &T) -> Result<()> {
+        match self {
+            RowWriter::Csv(writer) => writer.serialize(row)?,
+            RowWriter::Jsonl(writer) => {
+                serde_json::to_writer(&mut *writer, row)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            RowWriter::Csv(mut writer) => writer.flush()?,
+            RowWriter::Jsonl(mut writer) => writer.flush()?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes rows as they are read, so the whole table is never in memory
+async fn write_rows<T, R>(
+    rows: impl Stream<Item = Result<T, DbErr>>,
+    mut writer: RowWriter,
+    map: impl Fn(T) -> R,
+) -> Result<u64>
+where
+    R: Serialize,
+{
+    let mut rows = Box::pin(rows);
+    let mut count = 0;
+
+    while let Some(row) = rows.try_next().await? {
+        writer.write(&map(row))?;
+        count += 1;
+    }
+
+    writer.finish()?;
+
+    Ok(count)
+}
+
+impl Application {
+    /// Writes memes, users and votes of the chat to `memes`, `users` and `votes` files of the directory
+    pub async fn export(
+        &self,
+        chat_id: i64,
+        dates: (DateTime<Utc>, DateTime<Utc>),
+        format: ExportFormat,
+        dir: &Path,
+    ) -> Result<ExportStats> {
+        let (from, to) = dates;
+
+        std::fs::create_dir_all(dir)?;
+
+        Ok(ExportStats {
+            memes: write_rows(
+                Memes::export(chat_id, from, to).await?,
+                RowWriter::create(dir, "memes", format)?,
+                MemeRecord::from,
+            )
+            .await?,
+            users: write_rows(
+                Users::export(chat_id, from, to).await?,
+                RowWriter::create(dir, "users", format)?,
+                |user| user,
+            )
+            .await?,
+            votes: write_rows(
+                MemeLikes::export(chat_id, from, to).await?,
+                RowWriter::create(dir, "votes", format)?,
+                |vote| vote,
+            )
+            .await?,
+        })
+    }
+}
@@ -1,14 +1,189 @@
+use std::str::FromStr;
+
 opencv::opencv_branch_4! {
     use opencv::{
-        core::{Mat, Size},
+        core::{self, Mat, Size},
         imgcodecs,
         imgcodecs::ImreadModes,
         imgproc,
         imgproc::InterpolationFlags,
         prelude::*,
+        videoio::{self, VideoCapture},
     };
 }
 
+/// Size of the pHash/dHash bit matrix, 8x8 = 64 bits.
+pub const SMALL_HASH_SIZE: i32 = 8;
+pub const SMALL_HASH_BITS: usize = (SMALL_HASH_SIZE * SMALL_HASH_SIZE) as usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// Grayscale → resize → mean threshold
+    Average,
+    /// Horizontal gradient between neighbour pixels
+    Difference,
+    /// Low frequencies of DCT compared to their median
+    Perceptual,
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "average" | "ahash" => Ok(HashAlgorithm::Average),
+            "difference" | "dhash" => Ok(HashAlgorithm::Difference),
+            "perceptual" | "phash" => Ok(HashAlgorithm::Perceptual),
+            _ => Err(format!("Unknown hash algorithm: {s}")),
+        }
+    }
+}
+
+impl HashAlgorithm {
+    pub fn hash(&self, image: &ImageHash, hash_size: i32) -> Option<Hash> {
+        match *self {
+            HashAlgorithm::Average => image.clone().resize(hash_size).threshold().hash(),
+            HashAlgorithm::Difference => image.clone().resize_to(hash_size + 1, hash_size).difference_hash(),
+            HashAlgorithm::Perceptual => image.clone().resize(hash_size * 4).dct().perceptual_hash(hash_size),
+        }
+    }
+
+    /// Picks the algorithm's hash from database columns.
+    /// Bytes of the long hash don't keep its length, so it is read as `long_bits` long.
+    pub fn pick_column(
+        &self,
+        long_hash: Option<&[u8]>,
+        perceptual_hash: Option<i64>,
+        difference_hash: Option<i64>,
+        long_bits: usize,
+    ) -> Option<Hash> {
+        match *self {
+            HashAlgorithm::Average => long_hash.and_then(|bytes| Hash::from_bytes(bytes, long_bits)),
+            HashAlgorithm::Difference => difference_hash.map(|h| Hash::from_i64(h, SMALL_HASH_BITS)),
+            HashAlgorithm::Perceptual => perceptual_hash.map(|h| Hash::from_i64(h, SMALL_HASH_BITS)),
+        }
+    }
+}
+
+/// Bit string of an image hash, packed into big-endian `u64` words.
+///
+/// Bits are read as one big-endian number, so a hash shorter than 64 bits
+/// is right-aligned in its word (the same as Postgres `bit(64)::bigint` does).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Hash {
+    words: Vec<u64>,
+    bits: usize,
+}
+
+impl Hash {
+    pub fn from_bits(bits: impl IntoIterator<Item = bool>) -> Self {
+        let bits = bits.into_iter().collect::<Vec<bool>>();
+        let mut words = vec![0u64; bits.len().div_ceil(64)];
+        let offset = words.len() * 64 - bits.len();
+
+        for (i, bit) in bits.iter().enumerate() {
+            if *bit {
+                let pos = offset + i;
+                words[pos / 64] |= 1 << (63 - pos % 64);
+            }
+        }
+
+        Self {
+            words,
+            bits: bits.len(),
+        }
+    }
+
+    /// Reverse of `to_bytes`, `None` if the bytes are of a hash of another length
+    pub fn from_bytes(bytes: &[u8], bits: usize) -> Option<Self> {
+        if bytes.len() != bits.div_ceil(8) {
+            return None;
+        }
+
+        let padding = bytes.len() * 8 - bits;
+
+        Some(Self::from_bits(
+            bytes
+                .iter()
+                .flat_map(|b| (0..8).rev().map(move |i| (b >> i) & 1 == 1))
+                .skip(padding),
+        ))
+    }
+
+    pub fn from_i64(value: i64, bits: usize) -> Self {
+        Self::from_bits((0..bits).rev().map(|i| (value as u64 >> i) & 1 == 1))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let bytes = self.words.iter().flat_map(|w| w.to_be_bytes()).collect::<Vec<u8>>();
+
+        bytes[bytes.len() - self.bits.div_ceil(8)..].to_vec()
+    }
+
+    /// Only for hashes not longer than 64 bits
+    pub fn to_i64(&self) -> i64 {
+        self.words.last().copied().unwrap_or_default() as i64
+    }
+
+    pub fn bits(&self) -> usize {
+        self.bits
+    }
+
+    /// Percent of the same bits for a hash `distance` bits away from this one
+    pub fn similarity(&self, distance: u32) -> f64 {
+        if self.bits == 0 {
+            return 0f64;
+        }
+
+        (self.bits.saturating_sub(distance as usize) as f64 / self.bits as f64) * 100f64
+    }
+
+    /// Number of different bits, `None` for hashes of different sizes
+    pub fn distance(&self, other: &Hash) -> Option<u32> {
+        if self.bits != other.bits {
+            return None;
+        }
+
+        Some(
+            self.words
+                .iter()
+                .zip(other.words.iter())
+                .map(|(w1, w2)| (w1 ^ w2).count_ones())
+                .sum(),
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ImageHashes {
+    pub long_hash: Hash,
+    pub short_hash: Hash,
+    pub perceptual_hash: Hash,
+    pub difference_hash: Hash,
+}
+
+impl ImageHashes {
+    pub fn new(image: &ImageHash, long_size: i32, short_size: i32) -> Option<Self> {
+        let image = image.clone().grayscale();
+
+        Some(Self {
+            long_hash: HashAlgorithm::Average.hash(&image, long_size)?,
+            short_hash: HashAlgorithm::Average.hash(&image, short_size)?,
+            perceptual_hash: HashAlgorithm::Perceptual.hash(&image, SMALL_HASH_SIZE)?,
+            difference_hash: HashAlgorithm::Difference.hash(&image, SMALL_HASH_SIZE)?,
+        })
+    }
+
+    /// Hash which is used to compare images by the given algorithm
+    pub fn get(&self, algorithm: HashAlgorithm) -> &Hash {
+        match algorithm {
+            HashAlgorithm::Average => &self.long_hash,
+            HashAlgorithm::Difference => &self.difference_hash,
+            HashAlgorithm::Perceptual => &self.perceptual_hash,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct ImageHash {
     image: Mat,
@@ -21,6 +196,29 @@ impl ImageHash {
         }
     }
 
+    /// Evenly spaced frames of the video, without first and last ones
+    pub fn from_video(filename: &str, frames: usize) -> Vec<Self> {
+        let mut capture = match VideoCapture::from_file(filename, videoio::CAP_ANY) {
+            Ok(c) if c.is_opened().unwrap_or(false) => c,
+            _ => return Vec::new(),
+        };
+        let frames_count = capture.get(videoio::CAP_PROP_FRAME_COUNT).unwrap_or_default();
+
+        (1..=frames)
+            .filter_map(|i| {
+                let position = (frames_count * i as f64 / (frames + 1) as f64).floor();
+                let mut frame = Mat::default();
+
+                capture.set(videoio::CAP_PROP_POS_FRAMES, position).ok()?;
+
+                match capture.read(&mut frame) {
+                    Ok(true) => Some(Self { image: frame }),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
     pub fn grayscale(mut self) -> Self {
         let mut gray = Mat::default();
         imgproc::cvt_color(&self.image, &mut gray, imgproc::COLOR_BGR2GRAY, 0).unwrap_or_default();
@@ -29,12 +227,16 @@ impl ImageHash {
         self
     }
 
-    pub fn resize(mut self, hash_size: i32) -> Self {
+    pub fn resize(self, hash_size: i32) -> Self {
+        self.resize_to(hash_size, hash_size)
+    }
+
+    pub fn resize_to(mut self, width: i32, height: i32) -> Self {
         let mut resized = Mat::default();
         imgproc::resize(
             &self.image,
             &mut resized,
-            Size::new(hash_size, hash_size),
+            Size::new(width, height),
             0.0,
             0.0,
             InterpolationFlags::INTER_AREA as i32,
@@ -54,37 +256,73 @@ impl ImageHash {
         self
     }
 
-    pub fn hash(&self) -> Option<String> {
-        let a_image = self.image.to_vec_2d::<u8>();
+    pub fn dct(mut self) -> Self {
+        let mut f_image = Mat::default();
+        let mut d_image = Mat::default();
+
+        self.image
+            .convert_to(&mut f_image, core::CV_32F, 1.0, 0.0)
+            .unwrap_or_default();
+        core::dct(&f_image, &mut d_image, 0).unwrap_or_default();
+
+        self.image = d_image;
+        self
+    }
+
+    pub fn hash(&self) -> Option<Hash> {
+        let a_image = self.image.to_vec_2d::<u8>().ok()?;
+
+        Some(Hash::from_bits(a_image.iter().flatten().map(|item| *item == 255)))
+    }
+
+    pub fn difference_hash(&self) -> Option<Hash> {
+        let a_image = self.image.to_vec_2d::<u8>().ok()?;
 
-        if a_image.is_err() {
+        Some(Hash::from_bits(
+            a_image.iter().flat_map(|row| row.windows(2).map(|w| w[0] > w[1])),
+        ))
+    }
+
+    pub fn perceptual_hash(&self, hash_size: i32) -> Option<Hash> {
+        let a_image = self.image.to_vec_2d::<f32>().ok()?;
+        let size = hash_size as usize;
+
+        if a_image.len() < size || a_image.iter().any(|row| row.len() < size) {
             return None;
         }
 
-        let hash = a_image
-            .unwrap()
+        let low_freq = a_image
             .iter()
-            .map(|row| {
-                row.iter()
-                    .map(|item| {
-                        if *item == 255 {
-                            String::from("1")
-                        } else {
-                            String::from("0")
-                        }
-                    })
-                    .collect::<Vec<String>>()
-                    .join("")
-            })
-            .collect::<Vec<String>>()
-            .join("");
+            .take(size)
+            .flat_map(|row| row.iter().take(size).copied())
+            .collect::<Vec<f32>>();
+
+        // DC coefficient is skipped, it's only the average brightness
+        let mut sorted = low_freq[1..].to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let median = sorted[sorted.len() / 2];
+
+        Some(Hash::from_bits(low_freq.iter().map(|item| *item > median)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_keep_length_not_divisible_by_8() {
+        let hash = Hash::from_bits((0..100).map(|i| i % 3 == 0));
+        let bytes = hash.to_bytes();
 
-        Some(hash)
+        assert_eq!(bytes.len(), 13);
+        assert_eq!(Hash::from_bytes(&bytes, 100), Some(hash));
     }
 
-    pub fn compare_hashes(hash1: &str, hash2: &str) -> f64 {
-        let diffs_num = hash1.chars().zip(hash2.chars()).filter(|(c1, c2)| c1 != c2).count();
+    #[test]
+    fn bytes_of_other_length_are_rejected() {
+        let hash = Hash::from_bits((0..1024).map(|i| i % 2 == 0));
 
-        ((hash1.len() - diffs_num) as f64 / hash1.len() as f64) * 100f64
+        assert_eq!(Hash::from_bytes(&hash.to_bytes(), 100), None);
     }
 }
@@ -0,0 +1,146 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime};
+use serde::Deserialize;
+use std::path::Path;
+
+use super::Application;
+use crate::database::entity::{prelude::*, users};
+use sea_orm::Set;
+
+/// Chat history of the `result.json` file of Telegram Desktop
+#[derive(Deserialize)]
+struct ChatExport {
+    messages: Vec<ExportedMessage>,
+}
+
+#[derive(Deserialize)]
+struct ExportedMessage {
+    id: i64,
+    #[serde(rename = "type")]
+    kind: String,
+    /// Local time of the exporting computer
+    date: NaiveDateTime,
+    date_unixtime: Option<String>,
+    from: Option<String>,
+    /// Like `user123` or `channel123`
+    from_id: Option<String>,
+    /// Relative to the export directory
+    photo: Option<String>,
+    /// String or list of strings and formatted parts
+    #[serde(default)]
+    text: serde_json::Value,
+}
+
+impl ExportedMessage {
+    fn user_id(&self) -> Option<i64> {
+        self.from_id.as_deref()?.strip_prefix("user")?.parse().ok()
+    }
+
+    fn posted_at(&self) -> NaiveDateTime {
+        self.date_unixtime
+            .as_deref()
+            .and_then(|t| t.parse().ok())
+            .and_then(|t| DateTime::from_timestamp(t, 0))
+            .map_or(self.date, |t| t.naive_utc())
+    }
+
+    fn caption(&self) -> Option<String> {
+        let text = match &self.text {
+            serde_json::Value::String(text) => text.clone(),
+            serde_json::Value::Array(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    serde_json::Value::String(text) => Some(text.as_str()),
+                    part => part.get("text").and_then(|t| t.as_str()),
+                })
+                .collect(),
+            _ => String::new(),
+        };
+
+        Some(text).filter(|t| !t.is_empty())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ImportStats {
+    pub imported: usize,
+    /// Already known memes and photos not sent by users
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+impl Application {
+    /// Adds photos of a chat export as memes, so reposts of old memes are found.
+    ///
+    /// Memes are not posted again and albums are imported as separate photos.
+    /// Messages already known by their id are skipped, so the import can be repeated.
+    pub async fn import(&self, chat_id: i64, path: &Path) -> Result<ImportStats> {
+        let export: ChatExport = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut stats = ImportStats::default();
+
+        for message in export.messages.iter().filter(|m| m.kind == "message") {
+            let Some(photo) = &message.photo else {
+                continue;
+            };
+
+            let Some(user_id) = message.user_id() else {
+                stats.skipped += 1;
+                continue;
+            };
+
+            if Memes::get_by_msg_id(chat_id, message.id as u64).await.is_some() {
+                stats.skipped += 1;
+                continue;
+            }
+
+            match self.import_photo(chat_id, user_id, message, &dir.join(photo)).await {
+                Ok(()) => stats.imported += 1,
+                Err(e) => {
+                    warn!("Can't import message {}: {e}", message.id);
+                    stats.failed += 1;
+                }
+            }
+
+            if (stats.imported + stats.failed) % 100 == 0 {
+                info!("Imported {} memes, {} failed", stats.imported, stats.failed);
+            }
+        }
+
+        Ok(stats)
+    }
+
+    async fn import_photo(&self, chat_id: i64, user_id: i64, message: &ExportedMessage, photo: &Path) -> Result<()> {
+        // Photos which were not downloaded are replaced with a note in the export
+        if !photo.is_file() {
+            return Err(anyhow!("No photo file {}", photo.display()));
+        }
+
+        let hashes = self
+            .hash_file(&photo.to_string_lossy())
+            .ok_or_else(|| anyhow!("Error in opencv hashing"))?;
+
+        let user = users::ActiveModel {
+            user_id: Set(user_id),
+            firstname: Set(message.from.clone().unwrap_or_default()),
+            ..Default::default()
+        };
+
+        if !Users::add_if_absent(user).await {
+            return Err(anyhow!("Can't add user {user_id}"));
+        }
+
+        Memes::add_imported(
+            chat_id,
+            user_id,
+            message.id,
+            message.posted_at(),
+            message.caption(),
+            &hashes,
+        )
+        .await
+        .ok_or_else(|| anyhow!("Can't add meme"))?;
+
+        Ok(())
+    }
+}
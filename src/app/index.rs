@@ -0,0 +1,349 @@
+use std::collections::{hash_map::Entry, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use super::imghash::{Hash, HashAlgorithm, ImageHashes};
+use crate::database::entity::{
+    memes,
+    prelude::{MemeHashes, Memes},
+};
+use crate::redis::RedisManager;
+
+/// BK-tree over image hashes with Hamming distance as the metric.
+#[derive(Debug, Default)]
+pub struct BkTree {
+    root: Option<BkNode>,
+    size: usize,
+}
+
+#[derive(Debug)]
+struct BkNode {
+    hash: Hash,
+    uuid: Uuid,
+    children: HashMap<u32, BkNode>,
+}
+
+impl BkTree {
+    pub fn insert(&mut self, hash: &Hash, uuid: Uuid) {
+        let mut node = match self.root.as_mut() {
+            Some(node) => node,
+            None => {
+                self.root = Some(BkNode::new(hash, uuid));
+                self.size += 1;
+                return;
+            }
+        };
+
+        loop {
+            let Some(distance) = node.hash.distance(hash) else {
+                warn!("Hash of {uuid} has wrong length for index");
+                return;
+            };
+
+            if distance == 0 && node.uuid == uuid {
+                return;
+            }
+
+            match node.children.entry(distance) {
+                Entry::Occupied(entry) => node = entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    entry.insert(BkNode::new(hash, uuid));
+                    self.size += 1;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// All items within `max_distance` from `hash`, nearest first
+    pub fn find(&self, hash: &Hash, max_distance: u32) -> Vec<(Uuid, u32)> {
+        self.search(hash, max_distance).0
+    }
+
+    /// Found items and how many hashes were compared to find them
+    fn search(&self, hash: &Hash, max_distance: u32) -> (Vec<(Uuid, u32)>, usize) {
+        let mut found = Vec::new();
+        let mut compared = 0;
+        let mut stack: Vec<&BkNode> = self.root.iter().collect();
+
+        while let Some(node) = stack.pop() {
+            // Nothing can be pruned by a node which can't be compared
+            let Some(distance) = node.hash.distance(hash) else {
+                stack.extend(node.children.values());
+                continue;
+            };
+
+            compared += 1;
+
+            if distance <= max_distance {
+                found.push((node.uuid, distance));
+            }
+
+            let lower = distance.saturating_sub(max_distance);
+            let upper = distance + max_distance;
+
+            stack.extend(
+                node.children
+                    .iter()
+                    .filter(|(d, _)| (lower..=upper).contains(*d))
+                    .map(|(_, child)| child),
+            );
+        }
+
+        found.sort_by_key(|(_, distance)| *distance);
+        (found, compared)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl BkNode {
+    fn new(hash: &Hash, uuid: Uuid) -> Self {
+        Self {
+            hash: hash.clone(),
+            uuid,
+            children: HashMap::new(),
+        }
+    }
+}
+
+/// Per chat indexes of meme hashes, lazily built from `memes` table.
+/// Only hashes of the configured length are indexed.
+/// All indexes are dropped when the generation in Redis is changed by CLI commands.
+#[derive(Debug)]
+pub struct MemeIndex {
+    algorithm: HashAlgorithm,
+    bits: usize,
+    generation: AtomicU64,
+    trees: RwLock<HashMap<i64, BkTree>>,
+}
+
+impl MemeIndex {
+    pub fn new(algorithm: HashAlgorithm, bits: usize) -> Self {
+        Self {
+            algorithm,
+            bits,
+            generation: AtomicU64::new(0),
+            trees: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub async fn find(&self, chat_id: i64, hash: &Hash, max_distance: u32) -> Vec<(Uuid, u32)> {
+        self.invalidate().await;
+        self.load(chat_id).await;
+
+        let start = Instant::now();
+        let trees = self.trees.read().await;
+        let found = trees
+            .get(&chat_id)
+            .map(|tree| tree.find(hash, max_distance))
+            .unwrap_or_default();
+
+        debug!(
+            "Index search in chat {chat_id} took {:?}, found {} of {} hashes",
+            start.elapsed(),
+            found.len(),
+            trees.get(&chat_id).map(BkTree::size).unwrap_or_default()
+        );
+
+        found
+    }
+
+    pub async fn insert(&self, meme: &memes::Model, hashes: &[ImageHashes]) {
+        // Not loaded chats will get this meme from database on first search
+        if let Some(tree) = self.trees.write().await.get_mut(&meme.chat_id) {
+            for hash in hashes.iter().map(|h| h.get(self.algorithm)) {
+                if hash.bits() == self.bits {
+                    tree.insert(hash, meme.uuid);
+                }
+            }
+        }
+    }
+
+    async fn invalidate(&self) {
+        let generation = RedisManager::global().get_index_generation();
+
+        if self.generation.swap(generation, Ordering::Relaxed) != generation {
+            let mut trees = self.trees.write().await;
+
+            if !trees.is_empty() {
+                info!("Hashes are changed, indexes of {} chats will be rebuilt", trees.len());
+                trees.clear();
+            }
+        }
+    }
+
+    async fn load(&self, chat_id: i64) {
+        if self.trees.read().await.contains_key(&chat_id) {
+            return;
+        }
+
+        // Write lock is held while loading, so memes inserted meanwhile are not lost
+        let mut trees = self.trees.write().await;
+
+        if trees.contains_key(&chat_id) {
+            return;
+        }
+
+        let start = Instant::now();
+        let mut tree = BkTree::default();
+        let mut skipped = 0;
+
+        let hashes = Memes::get_hashed(chat_id)
+            .await
+            .into_iter()
+            .map(|meme| (meme.uuid, meme.hash(self.algorithm, self.bits)))
+            .chain(
+                MemeHashes::get_by_chat(chat_id)
+                    .await
+                    .into_iter()
+                    .map(|meme_hash| (meme_hash.meme_uuid, meme_hash.hash(self.algorithm, self.bits))),
+            );
+
+        for (uuid, hash) in hashes {
+            match hash {
+                Some(hash) if hash.bits() == self.bits => tree.insert(&hash, uuid),
+                _ => skipped += 1,
+            }
+        }
+
+        if skipped > 0 {
+            warn!("{skipped} hashes of chat {chat_id} are missing or of another size, rehash them to find their memes");
+        }
+
+        info!(
+            "Index of chat {chat_id} is built with {} hashes in {:?}",
+            tree.size(),
+            start.elapsed()
+        );
+
+        trees.insert(chat_id, tree);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn random_hash(rng: &mut StdRng, bits: usize) -> Hash {
+        Hash::from_bits((0..bits).map(|_| rng.gen::<bool>()))
+    }
+
+    /// The same hash with `count` random bits flipped, some of them may be flipped twice
+    fn flip(rng: &mut StdRng, hash: &Hash, count: usize) -> Hash {
+        let padding = hash.to_bytes().len() * 8 - hash.bits();
+        let mut bits = hash
+            .to_bytes()
+            .iter()
+            .flat_map(|b| (0..8).rev().map(move |i| (b >> i) & 1 == 1))
+            .skip(padding)
+            .collect::<Vec<bool>>();
+
+        for _ in 0..count {
+            let i = rng.gen_range(0..bits.len());
+            bits[i] = !bits[i];
+        }
+
+        Hash::from_bits(bits)
+    }
+
+    fn brute_force(items: &[(Hash, Uuid)], hash: &Hash, max_distance: u32) -> Vec<(Uuid, u32)> {
+        let mut found = items
+            .iter()
+            .filter_map(|(h, uuid)| h.distance(hash).map(|d| (*uuid, d)))
+            .filter(|(_, d)| *d <= max_distance)
+            .collect::<Vec<_>>();
+
+        found.sort();
+        found
+    }
+
+    /// Random hashes with near duplicates of some of them
+    fn items(rng: &mut StdRng, bits: usize, count: usize) -> Vec<(Hash, Uuid)> {
+        let mut items: Vec<(Hash, Uuid)> = Vec::with_capacity(count);
+
+        for i in 0..count {
+            let hash = match i % 4 {
+                0 => random_hash(rng, bits),
+                _ => {
+                    let (hash, _) = &items[rng.gen_range(0..items.len())];
+                    let count = rng.gen_range(0..bits / 8);
+                    flip(rng, hash, count)
+                }
+            };
+
+            items.push((hash, Uuid::from_u128(i as u128)));
+        }
+
+        items
+    }
+
+    fn tree(items: &[(Hash, Uuid)]) -> BkTree {
+        let mut tree = BkTree::default();
+
+        for (hash, uuid) in items {
+            tree.insert(hash, *uuid);
+        }
+
+        tree
+    }
+
+    #[test]
+    fn finds_the_same_as_brute_force() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        for bits in [64, 256] {
+            let items = items(&mut rng, bits, 3000);
+            let tree = tree(&items);
+
+            assert_eq!(tree.size(), items.len());
+
+            for _ in 0..200 {
+                let (hash, _) = &items[rng.gen_range(0..items.len())];
+                let count = rng.gen_range(0..bits / 8);
+                let query = flip(&mut rng, hash, count);
+                let max_distance = rng.gen_range(0..=bits as u32 / 6);
+
+                let mut found = tree.find(&query, max_distance);
+                let distances = found.iter().map(|(_, d)| *d).collect::<Vec<_>>();
+
+                assert!(distances.windows(2).all(|w| w[0] <= w[1]), "Not nearest first");
+
+                found.sort();
+                assert_eq!(found, brute_force(&items, &query, max_distance));
+            }
+        }
+    }
+
+    #[test]
+    fn hash_of_another_length_is_not_found() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let items = items(&mut rng, 64, 100);
+        let tree = tree(&items);
+
+        assert!(tree.find(&random_hash(&mut rng, 100), 100).is_empty());
+    }
+
+    #[test]
+    fn compares_less_than_brute_force() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let items = items(&mut rng, 64, 20000);
+        let tree = tree(&items);
+
+        for _ in 0..200 {
+            let (hash, _) = &items[rng.gen_range(0..items.len())];
+            let query = flip(&mut rng, hash, 2);
+            let (mut found, compared) = tree.search(&query, 4);
+
+            found.sort();
+            assert_eq!(found, brute_force(&items, &query, 4));
+            assert!(compared < items.len() / 2, "Compared {compared} of {}", items.len());
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use once_cell::sync::{Lazy, OnceCell};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+use teloxide::types::User;
+
+/// Language of chats without their own one
+pub static INSTANCE: OnceCell<Language> = OnceCell::new();
+
+/// Texts of each language by their keys
+static CATALOGS: Lazy<HashMap<Language, HashMap<String, String>>> = Lazy::new(|| {
+    HashMap::from([
+        (Language::Ru, parse(include_str!("../../locales/ru.txt"))),
+        (Language::En, parse(include_str!("../../locales/en.txt"))),
+    ])
+});
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Language {
+    #[default]
+    Ru,
+    En,
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "ru" => Ok(Language::Ru),
+            "en" => Ok(Language::En),
+            _ => Err(format!("Unknown language: {s}")),
+        }
+    }
+}
+
+/// CLDR plural categories which the languages use for integers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PluralCategory {
+    One,
+    Few,
+    Many,
+    Other,
+}
+
+impl PluralCategory {
+    fn name(&self) -> &'static str {
+        match *self {
+            PluralCategory::One => "one",
+            PluralCategory::Few => "few",
+            PluralCategory::Many => "many",
+            PluralCategory::Other => "other",
+        }
+    }
+}
+
+impl Language {
+    pub fn global() -> Language {
+        INSTANCE.get().copied().unwrap_or_default()
+    }
+
+    /// Language of the Telegram client of the user, the default one if the bot doesn't speak it
+    pub fn of_user(user: &User) -> Language {
+        user.language_code
+            .as_deref()
+            .and_then(|code| code.split('-').next())
+            .and_then(|code| code.parse().ok())
+            .unwrap_or_else(Language::global)
+    }
+
+    pub fn code(&self) -> &'static str {
+        match *self {
+            Language::Ru => "ru",
+            Language::En => "en",
+        }
+    }
+
+    pub fn plural_category(&self, num: i64) -> PluralCategory {
+        let num = num.unsigned_abs();
+
+        match *self {
+            Language::Ru => match (num % 10, num % 100) {
+                (1, n) if n != 11 => PluralCategory::One,
+                (2..=4, n) if !(12..=14).contains(&n) => PluralCategory::Few,
+                _ => PluralCategory::Many,
+            },
+            Language::En => match num {
+                1 => PluralCategory::One,
+                _ => PluralCategory::Other,
+            },
+        }
+    }
+
+    /// Text of the key, the one of the default language if this one has no such key
+    pub fn text(&self, key: &str) -> String {
+        self.lookup(key).unwrap_or_else(|| {
+            warn!("No text {key} in {} catalog", self.code());
+            key.to_string()
+        })
+    }
+
+    /// Text of the key with `{name}` placeholders replaced by the arguments
+    pub fn format(&self, key: &str, args: &[(&str, &(dyn Display + Sync))]) -> String {
+        args.iter().fold(self.text(key), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), &value.to_string())
+        })
+    }
+
+    /// Number with the form of the key for it, like `5 лайков` of `likes.one`, `likes.few` and `likes.many`.
+    /// Forms fall back to `other`.
+    pub fn plural(&self, key: &str, num: i64) -> String {
+        let form = format!("{key}.{}", self.plural_category(num).name());
+        let text = self
+            .lookup(&form)
+            .or_else(|| self.lookup(&format!("{key}.other")))
+            .unwrap_or_else(|| {
+                warn!("No text {form} in {} catalog", self.code());
+                form
+            });
+
+        text.replace("{count}", &num.to_string())
+    }
+
+    fn lookup(&self, key: &str) -> Option<String> {
+        CATALOGS
+            .get(self)
+            .and_then(|catalog| catalog.get(key))
+            .or_else(|| CATALOGS.get(&Language::global()).and_then(|catalog| catalog.get(key)))
+            .cloned()
+    }
+}
+
+/// Lines like `key = text`, `\n` in texts are line breaks. Empty lines and ones starting with `#` are skipped.
+fn parse(catalog: &str) -> HashMap<String, String> {
+    catalog
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, text)| (key.trim().to_string(), text.trim().replace("\\n", "\n")))
+        .collect()
+}
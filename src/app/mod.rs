@@ -1,18 +1,35 @@
 use anyhow::{anyhow, Result};
+use calendar::CalendarConfig;
 use envconfig::Envconfig;
-use futures::executor::block_on;
-use imghash::ImageHash;
-use std::{thread::sleep, time::Duration};
-use teloxide::prelude::*;
-use utils::from_binary_to_hex;
+use imghash::{Hash, HashAlgorithm, ImageHash, ImageHashes, SMALL_HASH_BITS};
+use index::MemeIndex;
+use locale::Language;
+use std::{collections::HashMap, str::FromStr, sync::Arc, thread::sleep, time::Duration};
+use teloxide::{prelude::*, types::Chat};
+use uuid::Uuid;
 
 use crate::bot::{BotConfig, BotManager};
 use crate::database::entity::{memes, prelude::*};
 use crate::redis::RedisManager;
+use crate::scheduler::SchedulerConfig;
 
+pub mod calendar;
+pub mod chart;
+pub mod collage;
+pub mod export;
 pub mod imghash;
+pub mod import;
+pub mod index;
+pub mod locale;
+pub mod rehash;
+pub mod replay;
+pub mod template;
 pub mod utils;
 
+/// How many frames of a video are hashed
+const VIDEO_FRAMES: usize = 5;
+
+#[derive(Default)]
 pub struct SimilarMeme {
     pub percent: i64,
     pub meme: Option<memes::Model>,
@@ -21,6 +38,7 @@ pub struct SimilarMeme {
 #[derive(Clone, Debug)]
 pub struct Application {
     pub config: Config,
+    pub index: Arc<MemeIndex>,
 }
 
 #[derive(Envconfig, Clone, Debug)]
@@ -31,100 +49,327 @@ pub struct Config {
     pub db_url: String,
     #[envconfig(from = "REDIS_URL")]
     pub redis_url: String,
+    /// Language of chats without their own one
+    #[envconfig(from = "LANGUAGE", default = "ru")]
+    pub language: Language,
+    #[envconfig(from = "HASH_ALGORITHM", default = "average")]
+    pub hash_algorithm: HashAlgorithm,
+    #[envconfig(nested)]
+    pub similarity: SimilarityConfig,
+    #[envconfig(nested)]
+    pub accordion: AccordionConfig,
+    #[envconfig(nested)]
+    pub calendar: CalendarConfig,
+    #[envconfig(nested)]
+    pub scheduler: SchedulerConfig,
     #[envconfig(nested)]
     pub bot: BotConfig,
 }
 
+#[derive(Envconfig, Clone, Debug)]
+pub struct SimilarityConfig {
+    /// Default for chats without their own thresholds
+    #[envconfig(from = "SIMILAR_THRESHOLD", default = "93")]
+    pub similar_threshold: f64,
+    #[envconfig(from = "DUPLICATE_THRESHOLD", default = "99")]
+    pub duplicate_threshold: f64,
+    /// Side of the average hash matrix, its square must be divisible by 8.
+    /// Stored hashes of another size are left out of the index until they are rehashed.
+    #[envconfig(from = "LONG_HASH_SIZE", default = "32")]
+    pub long_hash_size: i32,
+    /// Not more than 8, the hash is stored as bigint
+    #[envconfig(from = "SHORT_HASH_SIZE", default = "4")]
+    pub short_hash_size: i32,
+}
+
+impl SimilarityConfig {
+    /// Sizes which hashes can be stored with
+    pub fn validate(&self) -> Result<(), String> {
+        let long = self.long_hash_size;
+        let short = self.short_hash_size;
+
+        if long <= 0 || (long * long) % 8 != 0 {
+            return Err(format!(
+                "LONG_HASH_SIZE={long}, its square must be a positive multiple of 8"
+            ));
+        }
+
+        if !(1..=8).contains(&short) {
+            return Err(format!(
+                "SHORT_HASH_SIZE={short}, it must be from 1 to 8 to fit 64 bits"
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Length of the hashes which the algorithm compares
+    pub fn hash_bits(&self, algorithm: HashAlgorithm) -> usize {
+        match algorithm {
+            HashAlgorithm::Average => (self.long_hash_size * self.long_hash_size) as usize,
+            HashAlgorithm::Difference | HashAlgorithm::Perceptual => SMALL_HASH_BITS,
+        }
+    }
+
+    pub fn thresholds(&self) -> Thresholds {
+        Thresholds {
+            similar: self.similar_threshold,
+            duplicate: self.duplicate_threshold,
+        }
+    }
+}
+
+/// Complaints of members about reposts, sent with /accordion
+#[derive(Envconfig, Clone, Debug)]
+pub struct AccordionConfig {
+    /// Distinct members who decide that a meme is a repost
+    #[envconfig(from = "ACCORDION_VOTES", default = "3")]
+    pub votes: u64,
+    /// Older complaints are not counted
+    #[envconfig(from = "ACCORDION_WINDOW_HOURS", default = "24")]
+    pub window_hours: i64,
+    #[envconfig(from = "ACCORDION_ACTION", default = "remove")]
+    pub action: AccordionAction,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AccordionAction {
+    /// The meme is deleted from the chat and database
+    Remove,
+    /// The meme is kept, but left out of statistics
+    MarkRepost,
+}
+
+impl FromStr for AccordionAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "remove" => Ok(AccordionAction::Remove),
+            "repost" => Ok(AccordionAction::MarkRepost),
+            _ => Err(format!("Unknown accordion action: {s}")),
+        }
+    }
+}
+
+/// Percents of the same hash bits
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Thresholds {
+    /// Memes above it are similar
+    pub similar: f64,
+    /// Memes at or above it are the same
+    pub duplicate: f64,
+}
+
+impl Thresholds {
+    /// Farther hashes can't be similar
+    pub fn max_distance(&self, hash: &Hash) -> u32 {
+        (hash.bits() as f64 * (100f64 - self.similar) / 100f64) as u32
+    }
+
+    /// Percent for users: `None` if memes are not similar, 100 if they are the same
+    pub fn percent(&self, percent: f64) -> Option<i64> {
+        if percent <= self.similar {
+            return None;
+        }
+
+        Some(if percent >= self.duplicate { 100 } else { percent as i64 })
+    }
+}
+
 impl Application {
     pub fn new() -> Self {
         let config = Config::init_from_env().expect("Can't load config from environment");
 
-        Self { config }
+        if let Err(e) = config.similarity.validate() {
+            panic!("Wrong hash size: {e}");
+        }
+
+        let index = Arc::new(MemeIndex::new(
+            config.hash_algorithm,
+            config.similarity.hash_bits(config.hash_algorithm),
+        ));
+
+        Self { config, index }
     }
 
-    pub async fn generate_hashes(&self, file_id: &str) -> Result<(Option<String>, Option<String>)> {
+    pub async fn generate_hashes(&self, file_id: &str) -> Result<ImageHashes> {
         let path = BotManager::global().download_file(file_id).await?;
 
         sleep(Duration::from_millis(50)); // Sometimes downloading is very fast
         debug!("Filesize {path} is = {}", std::fs::metadata(&path)?.len());
 
-        let cv_image = ImageHash::new(&path).grayscale();
-        let hash = cv_image.clone().resize(32).threshold().hash();
-        let hash_min = cv_image.resize(4).threshold().hash();
+        let hashes = self.hash_file(&path);
 
         std::fs::remove_file(&path).unwrap_or_default();
 
-        if hash.is_none() || hash_min.is_none() {
-            return Err(anyhow!("Error in opencv hashing"));
+        hashes.ok_or_else(|| anyhow!("Error in opencv hashing"))
+    }
+
+    /// Hashes of a local image with the configured sizes
+    pub fn hash_file(&self, path: &str) -> Option<ImageHashes> {
+        ImageHashes::new(
+            &ImageHash::new(path),
+            self.config.similarity.long_hash_size,
+            self.config.similarity.short_hash_size,
+        )
+    }
+
+    pub async fn generate_video_hashes(&self, file_id: &str) -> Result<Vec<ImageHashes>> {
+        let path = BotManager::global().download_file(file_id).await?;
+
+        sleep(Duration::from_millis(50)); // Sometimes downloading is very fast
+        debug!("Filesize {path} is = {}", std::fs::metadata(&path)?.len());
+
+        let hashes = ImageHash::from_video(&path, VIDEO_FRAMES)
+            .iter()
+            .filter_map(|frame| {
+                ImageHashes::new(
+                    frame,
+                    self.config.similarity.long_hash_size,
+                    self.config.similarity.short_hash_size,
+                )
+            })
+            .collect::<Vec<ImageHashes>>();
+
+        std::fs::remove_file(&path).unwrap_or_default();
+
+        if hashes.is_empty() {
+            return Err(anyhow!("Can't get frames from video"));
         }
 
-        Ok((
-            Some(from_binary_to_hex(&hash.unwrap())),
-            Some(from_binary_to_hex(&hash_min.unwrap())),
-        ))
+        Ok(hashes)
     }
 
-    pub async fn get_similar_meme(short_hash: &str, long_hash: &str) -> SimilarMeme {
-        let mut s_meme = SimilarMeme { percent: 0, meme: None };
+    /// Thresholds of the chat, or the default ones
+    pub async fn thresholds(&self, chat_id: i64) -> Thresholds {
+        let default = self.config.similarity.thresholds();
 
-        let similar_memes = Memes::get_by_short_hash(short_hash).await;
+        match Chats::get_by_id(chat_id).await {
+            Some(chat) => chat.thresholds(default),
+            None => default,
+        }
+    }
 
-        similar_memes.into_iter().for_each(|meme| {
-            let meme_hash = meme.long_hash.clone().unwrap_or_default();
+    pub async fn get_similar_meme(&self, chat_id: i64, hashes: &ImageHashes) -> SimilarMeme {
+        let thresholds = self.thresholds(chat_id).await;
+        let hash = hashes.get(self.config.hash_algorithm);
 
-            if meme_hash.len() == long_hash.len() {
-                let percent = ImageHash::compare_hashes(
-                    &utils::from_hex_to_binary(long_hash),
-                    &utils::from_hex_to_binary(&meme_hash),
-                );
+        // Nearest memes go first
+        for (uuid, distance) in self.index.find(chat_id, hash, thresholds.max_distance(hash)).await {
+            if let Some(s_meme) = Self::to_similar_meme(uuid, hash.similarity(distance), &thresholds).await {
+                return s_meme;
+            }
+        }
 
-                if percent > 93f64 && percent < 99f64 {
-                    if percent as i64 > s_meme.percent {
-                        s_meme = SimilarMeme {
-                            percent: percent as i64,
-                            meme: Some(meme),
-                        };
-                    }
-                } else if percent >= 99f64 {
-                    s_meme = SimilarMeme {
-                        percent: 100,
-                        meme: Some(meme),
-                    };
-                }
+        SimilarMeme::default()
+    }
+
+    /// Video is similar to another one by the average similarity of its frames
+    pub async fn get_similar_video(&self, chat_id: i64, frames: &[ImageHashes]) -> SimilarMeme {
+        let thresholds = self.thresholds(chat_id).await;
+        let mut scores: HashMap<Uuid, f64> = HashMap::new();
+
+        for frame in frames {
+            let hash = frame.get(self.config.hash_algorithm);
+            let mut best: HashMap<Uuid, f64> = HashMap::new();
+
+            for (uuid, distance) in self.index.find(chat_id, hash, thresholds.max_distance(hash)).await {
+                let percent = hash.similarity(distance);
+                let entry = best.entry(uuid).or_default();
+
+                *entry = entry.max(percent);
+            }
+
+            for (uuid, percent) in best {
+                *scores.entry(uuid).or_default() += percent / frames.len() as f64;
             }
-        });
+        }
+
+        let mut scores = scores.into_iter().collect::<Vec<(Uuid, f64)>>();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        for (uuid, percent) in scores {
+            if let Some(s_meme) = Self::to_similar_meme(uuid, percent, &thresholds).await {
+                return s_meme;
+            }
+        }
+
+        SimilarMeme::default()
+    }
+
+    async fn to_similar_meme(uuid: Uuid, percent: f64, thresholds: &Thresholds) -> Option<SimilarMeme> {
+        let percent = thresholds.percent(percent)?;
 
-        s_meme
+        // Removed memes are still in the index
+        let meme = Memes::get_by_id(uuid).await?;
+
+        Some(SimilarMeme {
+            percent,
+            meme: Some(meme),
+        })
     }
 
-    pub fn check_version(&self) {
+    pub async fn check_version(&self) {
         let redis = RedisManager::global();
-        let chat_id = self.config.bot.chat_id;
+
         if let Some(redis_version) = redis.get_app_version() {
             if redis_version != self.config.app_version {
-                block_on(
-                    BotManager::global()
+                for chat in Chats::get_active().await {
+                    if let Err(e) = BotManager::global()
                         .get()
-                        .send_message(ChatId(chat_id), "😌 Я обновилься!")
-                        .send(),
-                )
-                .expect("Can't send message");
+                        .send_message(ChatId(chat.chat_id), chat.language().text("version-updated"))
+                        .await
+                    {
+                        warn!("Can't send version message to chat {}: {e}", chat.chat_id);
+                    }
+                }
             }
         }
 
         redis.set_app_version(&self.config.app_version);
     }
 
-    pub fn register_chat(&self) -> bool {
+    pub async fn register_chats(&self) {
+        if let Some(chat_id) = self.config.bot.chat_id {
+            match BotManager::global().get_chat(chat_id).await {
+                Ok(chat) => {
+                    Chats::add(&chat).await;
+                }
+                Err(e) => warn!("Can't get chat {chat_id} from config: {e}"),
+            }
+        }
+
+        for chat in Chats::get_active().await {
+            self.register_chat_id(chat.chat_id).await;
+        }
+    }
+
+    pub async fn register_chat(&self, chat: &Chat) -> bool {
+        if !Chats::add(chat).await {
+            return false;
+        }
+
+        self.register_chat_id(chat.id.0).await;
+        info!("Chat {} is registered", chat.id.0);
+
+        true
+    }
+
+    pub async fn unregister_chat(&self, chat_id: i64) {
+        RedisManager::global().unregister_chat(chat_id);
+        Chats::remove(chat_id).await;
+
+        info!("Chat {chat_id} is unregistered");
+    }
+
+    async fn register_chat_id(&self, chat_id: i64) {
         let redis = RedisManager::global();
-        let chat_id = self.config.bot.chat_id;
-        let admins = block_on(BotManager::global().get_chat_admins(chat_id));
+        let admins = BotManager::global().get_chat_admins(chat_id).await;
 
         redis.register_chat(chat_id);
         redis.set_chat_admins(chat_id, &admins);
 
-        ChatAdmins::add_admins(chat_id, &admins);
-
-        true
+        ChatAdmins::set_admins(chat_id, &admins).await;
     }
 }
@@ -0,0 +1,202 @@
+use anyhow::{anyhow, Result};
+use std::time::Duration;
+use teloxide::types::{Animation, PhotoSize, Sticker, Video};
+
+use super::imghash::ImageHashes;
+use super::Application;
+use crate::bot::types::AlbumItem;
+use crate::database::entity::{
+    memes::{self, MediaKind},
+    prelude::{MemeHashes, Memes},
+};
+use crate::redis::RedisManager;
+
+const PAGE_SIZE: u64 = 100;
+
+/// New hashes of a meme, stored where the bot stores them for its media kind
+enum Rehashed {
+    /// In the `memes` row
+    Meme(Option<ImageHashes>),
+    /// In `meme_hashes` rows
+    Frames(Vec<ImageHashes>),
+}
+
+#[derive(Debug, Default)]
+pub struct RehashStats {
+    pub rehashed: usize,
+    /// Rehashed memes which hashes of the current algorithm are not the same as stored ones
+    pub changed: usize,
+    /// Imported memes, they have no Telegram files to download
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+impl Application {
+    /// Recomputes hashes of stored memes with the current algorithm settings.
+    ///
+    /// Progress is kept in Redis, so an interrupted run continues from the last meme
+    /// before the first failed one, and a run with failures continues from it next time.
+    /// Every meme waits `delay` to stay within Telegram download limits.
+    /// Dry run only hashes memes and logs the changed ones, without saving hashes or progress.
+    ///
+    /// Memes imported from exports are skipped, as only their hashes are stored.
+    /// They are hashed again by removing them and importing the export again.
+    pub async fn rehash(&self, chat_id: Option<i64>, delay: Duration, dry_run: bool, restart: bool) -> RehashStats {
+        let redis = RedisManager::global();
+        let cursor_key = chat_id.map_or_else(|| String::from("all"), |id| id.to_string());
+        let mut stats = RehashStats::default();
+
+        if restart {
+            redis.clear_rehash_cursor(&cursor_key);
+        }
+
+        let mut after = redis.get_rehash_cursor(&cursor_key).and_then(|c| c.parse().ok());
+
+        if let Some(uuid) = after {
+            info!("Rehash continues after meme {uuid}");
+        }
+
+        loop {
+            let memes = Memes::get_page(chat_id, after, PAGE_SIZE).await;
+
+            if memes.is_empty() {
+                break;
+            }
+
+            for meme in &memes {
+                if meme.photos.is_none() {
+                    stats.skipped += 1;
+
+                    if !dry_run && stats.failed == 0 {
+                        redis.set_rehash_cursor(&cursor_key, &meme.uuid.to_string());
+                    }
+
+                    continue;
+                }
+
+                match self.rehash_meme(meme).await {
+                    Ok(rehashed) => {
+                        if self.is_changed(meme, &rehashed).await {
+                            stats.changed += 1;
+
+                            if dry_run {
+                                info!("Hashes of meme {} in chat {} would change", meme.uuid, meme.chat_id);
+                            }
+                        }
+
+                        if !dry_run && !Self::save_rehashed(meme, &rehashed).await {
+                            stats.failed += 1;
+                        } else {
+                            stats.rehashed += 1;
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Can't rehash meme {}: {e}", meme.uuid);
+                        stats.failed += 1;
+                    }
+                }
+
+                // The cursor stays before the first failed meme to try it again next time
+                if !dry_run && stats.failed == 0 {
+                    redis.set_rehash_cursor(&cursor_key, &meme.uuid.to_string());
+                }
+
+                tokio::time::sleep(delay).await;
+            }
+
+            after = memes.last().map(|m| m.uuid);
+            info!(
+                "Rehashed {} memes, {} skipped, {} failed",
+                stats.rehashed, stats.skipped, stats.failed
+            );
+        }
+
+        if !dry_run && stats.failed == 0 {
+            redis.clear_rehash_cursor(&cursor_key);
+        }
+
+        stats
+    }
+
+    /// Whether hashes of the current algorithm differ from the stored ones
+    async fn is_changed(&self, meme: &memes::Model, rehashed: &Rehashed) -> bool {
+        let algorithm = self.config.hash_algorithm;
+        let bits = self.config.similarity.hash_bits(algorithm);
+
+        match rehashed {
+            Rehashed::Meme(hashes) => meme.hash(algorithm, bits) != hashes.as_ref().map(|h| h.get(algorithm).clone()),
+            Rehashed::Frames(frames) => {
+                let stored = MemeHashes::get_by_meme(meme.uuid)
+                    .await
+                    .iter()
+                    .map(|h| h.hash(algorithm, bits))
+                    .collect::<Vec<_>>();
+
+                stored
+                    != frames
+                        .iter()
+                        .map(|h| Some(h.get(algorithm).clone()))
+                        .collect::<Vec<_>>()
+            }
+        }
+    }
+
+    async fn rehash_meme(&self, meme: &memes::Model) -> Result<Rehashed> {
+        let photos = meme.photos.clone().ok_or_else(|| anyhow!("Meme has no media"))?;
+
+        match meme.media_kind {
+            MediaKind::Photo => {
+                let photos: Vec<PhotoSize> = serde_json::from_value(photos)?;
+                let photo = photos.first().ok_or_else(|| anyhow!("Meme has no photos"))?;
+
+                Ok(Rehashed::Meme(Some(self.generate_hashes(&photo.file.id).await?)))
+            }
+            MediaKind::Album => {
+                let album: Vec<AlbumItem> = serde_json::from_value(photos)?;
+                let mut hashes = Vec::with_capacity(album.len());
+
+                for item in &album {
+                    match item {
+                        AlbumItem::Photo(photos) => {
+                            if let Some(photo) = photos.first() {
+                                hashes.push(self.generate_hashes(&photo.file.id).await?);
+                            }
+                        }
+                        AlbumItem::Video(video) => hashes.extend(self.generate_video_hashes(&video.file.id).await?),
+                    }
+                }
+
+                Ok(Rehashed::Frames(hashes))
+            }
+            MediaKind::Video => {
+                let video: Video = serde_json::from_value(photos)?;
+
+                Ok(Rehashed::Frames(self.generate_video_hashes(&video.file.id).await?))
+            }
+            MediaKind::Animation => {
+                let animation: Animation = serde_json::from_value(photos)?;
+
+                self.rehash_thumbnail(animation.thumbnail.as_ref()).await
+            }
+            MediaKind::Sticker => {
+                let sticker: Sticker = serde_json::from_value(photos)?;
+
+                self.rehash_thumbnail(sticker.thumbnail.as_ref()).await
+            }
+        }
+    }
+
+    async fn rehash_thumbnail(&self, thumbnail: Option<&PhotoSize>) -> Result<Rehashed> {
+        match thumbnail {
+            Some(thumbnail) => Ok(Rehashed::Meme(Some(self.generate_hashes(&thumbnail.file.id).await?))),
+            None => Ok(Rehashed::Meme(None)),
+        }
+    }
+
+    async fn save_rehashed(meme: &memes::Model, rehashed: &Rehashed) -> bool {
+        match rehashed {
+            Rehashed::Meme(hashes) => meme.replace_hashes(hashes.as_ref()).await,
+            Rehashed::Frames(frames) => MemeHashes::replace(meme.uuid, frames).await,
+        }
+    }
+}
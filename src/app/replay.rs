@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use super::imghash::Hash;
+use super::index::BkTree;
+use super::{Application, Thresholds};
+use crate::database::entity::{
+    memes::MediaKind,
+    prelude::{MemeHashes, Memes},
+};
+
+/// Best matches of stored memes with the memes posted before them
+pub struct ReplayReport {
+    pub memes: usize,
+    percents: Vec<f64>,
+}
+
+impl ReplayReport {
+    /// Numbers of duplicate and similar memes with the given thresholds
+    pub fn hits(&self, thresholds: &Thresholds) -> (usize, usize) {
+        self.percents
+            .iter()
+            .filter_map(|percent| thresholds.percent(*percent))
+            .fold((0, 0), |(duplicates, similar), percent| {
+                if percent == 100 {
+                    (duplicates + 1, similar)
+                } else {
+                    (duplicates, similar + 1)
+                }
+            })
+    }
+}
+
+impl Application {
+    /// Compares stored memes in the order they were posted, like the bot did.
+    /// Only stored hashes are used, so other hash sizes need a rehash first.
+    pub async fn replay(&self, chat_id: i64, loosest: &Thresholds) -> ReplayReport {
+        let algorithm = self.config.hash_algorithm;
+        let bits = self.config.similarity.hash_bits(algorithm);
+        let mut frames: HashMap<Uuid, Vec<Hash>> = HashMap::new();
+
+        for meme_hash in MemeHashes::get_by_chat(chat_id).await {
+            if let Some(hash) = meme_hash.hash(algorithm, bits) {
+                frames.entry(meme_hash.meme_uuid).or_default().push(hash);
+            }
+        }
+
+        let memes = Memes::get_by_chat(chat_id).await;
+        let mut tree = BkTree::default();
+        let mut percents = Vec::new();
+
+        for meme in &memes {
+            let hashes = match meme.hash(algorithm, bits) {
+                Some(hash) => vec![hash],
+                None => frames.remove(&meme.uuid).unwrap_or_default(),
+            };
+
+            if let Some(percent) = best_percent(&tree, &hashes, meme.media_kind == MediaKind::Video, loosest) {
+                percents.push(percent);
+            }
+
+            for hash in &hashes {
+                tree.insert(hash, meme.uuid);
+            }
+        }
+
+        ReplayReport {
+            memes: memes.len(),
+            percents,
+        }
+    }
+}
+
+/// Videos are scored by the average of their frames, other memes by the best picture
+fn best_percent(tree: &BkTree, hashes: &[Hash], average: bool, thresholds: &Thresholds) -> Option<f64> {
+    let mut scores: HashMap<Uuid, f64> = HashMap::new();
+
+    for hash in hashes {
+        let mut best: HashMap<Uuid, f64> = HashMap::new();
+
+        for (uuid, distance) in tree.find(hash, thresholds.max_distance(hash)) {
+            let entry = best.entry(uuid).or_default();
+
+            *entry = entry.max(hash.similarity(distance));
+        }
+
+        for (uuid, percent) in best {
+            let score = scores.entry(uuid).or_default();
+
+            *score = if average {
+                *score + percent / hashes.len() as f64
+            } else {
+                score.max(percent)
+            };
+        }
+    }
+
+    scores.into_values().reduce(f64::max)
+}
@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use teloxide::utils::html;
+
+use crate::database::entity::messages::EntityTypes;
+
+/// Values which stored messages can reference as `{name}`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Placeholder {
+    /// Mention of the user who sent the meme, joined or left the chat
+    UserName,
+    /// Mention of the author of the meme which was already sent
+    AuthorName,
+    /// How long ago the meme was already sent, like `3 days`
+    MemeAge,
+    /// Similarity of the memes, like `95 percent`
+    Percent,
+    ChatTitle,
+    /// Memes of the user in the chat, like `12 memes`
+    UserMemes,
+}
+
+impl Placeholder {
+    pub const ALL: [Placeholder; 6] = [
+        Placeholder::UserName,
+        Placeholder::AuthorName,
+        Placeholder::MemeAge,
+        Placeholder::Percent,
+        Placeholder::ChatTitle,
+        Placeholder::UserMemes,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Placeholder::UserName => "user_name",
+            Placeholder::AuthorName => "author_name",
+            Placeholder::MemeAge => "meme_age",
+            Placeholder::Percent => "percent",
+            Placeholder::ChatTitle => "chat_title",
+            Placeholder::UserMemes => "user_memes",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name() == name)
+    }
+
+    /// Placeholders which have values when messages of the type are sent
+    pub fn of(entity_type: &EntityTypes) -> &'static [Placeholder] {
+        match entity_type {
+            EntityTypes::MemeAlreadyExists => &[
+                Placeholder::UserName,
+                Placeholder::AuthorName,
+                Placeholder::MemeAge,
+                Placeholder::ChatTitle,
+                Placeholder::UserMemes,
+            ],
+            EntityTypes::SimilarMeme => &[
+                Placeholder::UserName,
+                Placeholder::AuthorName,
+                Placeholder::MemeAge,
+                Placeholder::Percent,
+                Placeholder::ChatTitle,
+                Placeholder::UserMemes,
+            ],
+            EntityTypes::NewbieUser | EntityTypes::UserLeftChat => {
+                &[Placeholder::UserName, Placeholder::ChatTitle, Placeholder::UserMemes]
+            }
+            EntityTypes::PressFToPrayRespects => &[],
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TemplateError {
+    /// `{` without `}`, at the char position
+    Unclosed(usize),
+    /// `}` without `{`, at the char position
+    Unopened(usize),
+    Unknown(String),
+    /// Known placeholder which has no value for the type of the message
+    NotAllowed(&'static str),
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::Unclosed(at) => write!(f, "unclosed {{ at {at}"),
+            TemplateError::Unopened(at) => write!(f, "unopened }} at {at}"),
+            TemplateError::Unknown(name) => write!(f, "unknown placeholder {{{name}}}"),
+            TemplateError::NotAllowed(name) => write!(f, "placeholder {{{name}}} can't be used here"),
+        }
+    }
+}
+
+/// Inline formatting tags which messages stored before templates may have
+const INLINE_TAGS: [&str; 11] = [
+    "b",
+    "strong",
+    "i",
+    "em",
+    "u",
+    "ins",
+    "s",
+    "strike",
+    "del",
+    "code",
+    "tg-spoiler",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Text(String),
+    Placeholder(Placeholder),
+    /// Opening or closing inline tag, like `<b>` or `</b>`
+    Tag(String),
+}
+
+/// Plain text with placeholders, `{{` and `}}` are literal braces.
+/// Balanced inline tags without attributes, like `<b>`, are kept as HTML.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template(Vec<Segment>);
+
+impl Template {
+    pub fn parse(text: &str) -> Result<Self, TemplateError> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().enumerate().peekable();
+
+        while let Some((at, c)) = chars.next() {
+            match c {
+                '{' if chars.next_if(|(_, c)| *c == '{').is_some() => literal.push('{'),
+                '}' if chars.next_if(|(_, c)| *c == '}').is_some() => literal.push('}'),
+                '}' => return Err(TemplateError::Unopened(at)),
+                '{' => {
+                    let mut name = String::new();
+
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) => break,
+                            Some((_, c)) => name.push(c),
+                            None => return Err(TemplateError::Unclosed(at)),
+                        }
+                    }
+
+                    let placeholder = Placeholder::from_name(name.trim()).ok_or(TemplateError::Unknown(name))?;
+
+                    if !literal.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut literal)));
+                    }
+
+                    segments.push(Segment::Placeholder(placeholder));
+                }
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Text(literal));
+        }
+
+        let tagged = segments
+            .iter()
+            .flat_map(|segment| match segment {
+                Segment::Text(text) => split_tags(text),
+                segment => vec![segment.clone()],
+            })
+            .collect::<Vec<Segment>>();
+
+        // Unbalanced tags would break the whole message, so they are shown as they are
+        if is_balanced(&tagged) {
+            Ok(Self(tagged))
+        } else {
+            Ok(Self(segments))
+        }
+    }
+
+    /// Parses a new message of the type, its placeholders must have values when it is sent
+    pub fn validate(text: &str, entity_type: &EntityTypes) -> Result<Self, TemplateError> {
+        let template = Self::parse(text)?;
+        let allowed = Placeholder::of(entity_type);
+
+        let not_allowed = template.placeholders().find(|p| !allowed.contains(p));
+
+        match not_allowed {
+            Some(placeholder) => Err(TemplateError::NotAllowed(placeholder.name())),
+            None => Ok(template),
+        }
+    }
+
+    pub fn placeholders(&self) -> impl Iterator<Item = Placeholder> + '_ {
+        self.0.iter().filter_map(|segment| match segment {
+            Segment::Placeholder(placeholder) => Some(*placeholder),
+            Segment::Text(_) | Segment::Tag(_) => None,
+        })
+    }
+
+    /// HTML of the message, placeholders without values are left empty
+    pub fn render(&self, values: &TemplateValues) -> String {
+        self.0
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => html::escape(text),
+                Segment::Placeholder(placeholder) => values.0.get(placeholder).cloned().unwrap_or_default(),
+                Segment::Tag(tag) => tag.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Text with inline tags as separate segments
+fn split_tags(text: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        let tag = rest[start..].find('>').map(|end| &rest[start..=start + end]);
+
+        match tag.filter(|tag| tag_name(tag).is_some()) {
+            Some(tag) => {
+                if start > 0 {
+                    segments.push(Segment::Text(rest[..start].to_string()));
+                }
+
+                segments.push(Segment::Tag(tag.to_string()));
+                rest = &rest[start + tag.len()..];
+            }
+            None => {
+                segments.push(Segment::Text(rest[..=start].to_string()));
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest.to_string()));
+    }
+
+    // Neighbouring texts are merged back
+    segments.into_iter().fold(Vec::new(), |mut merged, segment| {
+        match (merged.last_mut(), segment) {
+            (Some(Segment::Text(last)), Segment::Text(text)) => last.push_str(&text),
+            (_, segment) => merged.push(segment),
+        }
+        merged
+    })
+}
+
+/// Name of an inline tag and whether it is a closing one
+fn tag_name(tag: &str) -> Option<(&str, bool)> {
+    let inner = tag.strip_prefix('<')?.strip_suffix('>')?;
+    let (name, closing) = match inner.strip_prefix('/') {
+        Some(name) => (name, true),
+        None => (inner, false),
+    };
+
+    INLINE_TAGS.contains(&name).then_some((name, closing))
+}
+
+fn is_balanced(segments: &[Segment]) -> bool {
+    let mut open = Vec::new();
+
+    for segment in segments {
+        if let Segment::Tag(tag) = segment {
+            match tag_name(tag) {
+                Some((name, false)) => open.push(name),
+                Some((name, true)) if open.last() == Some(&name) => {
+                    open.pop();
+                }
+                _ => return false,
+            }
+        }
+    }
+
+    open.is_empty()
+}
+
+/// HTML of placeholders
+#[derive(Clone, Debug, Default)]
+pub struct TemplateValues(HashMap<Placeholder, String>);
+
+impl TemplateValues {
+    /// Plain text which is escaped
+    pub fn set_text(&mut self, placeholder: Placeholder, text: &str) {
+        self.0.insert(placeholder, html::escape(text));
+    }
+
+    /// Already safe HTML, like mentions of users
+    pub fn set_html(&mut self, placeholder: Placeholder, html: String) {
+        self.0.insert(placeholder, html);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(text: &str) -> String {
+        let mut values = TemplateValues::default();
+        values.set_text(Placeholder::ChatTitle, "<Memes>");
+
+        Template::parse(text).unwrap().render(&values)
+    }
+
+    #[test]
+    fn balanced_inline_tags_are_kept() {
+        assert_eq!(render("<b>Hi</b>, {chat_title}!"), "<b>Hi</b>, &lt;Memes&gt;!");
+        assert_eq!(render("<i><b>{chat_title}</b></i>"), "<i><b>&lt;Memes&gt;</b></i>");
+    }
+
+    #[test]
+    fn other_tags_are_escaped() {
+        assert_eq!(render("<b>Hi</i>"), "&lt;b&gt;Hi&lt;/i&gt;");
+        assert_eq!(render("<b>Hi"), "&lt;b&gt;Hi");
+        assert_eq!(
+            render("<a href=\"x\">Hi</a> 1 < 2"),
+            "&lt;a href=\"x\"&gt;Hi&lt;/a&gt; 1 &lt; 2"
+        );
+    }
+
+    #[test]
+    fn braces_are_doubled_for_literals() {
+        assert_eq!(
+            render("{{chat_title}} is {chat_title}"),
+            "{chat_title} is &lt;Memes&gt;"
+        );
+        assert_eq!(render("}}{{"), "}{");
+    }
+
+    #[test]
+    fn unknown_placeholder_is_an_error() {
+        assert_eq!(
+            Template::parse("Hi, {nobody}"),
+            Err(TemplateError::Unknown("nobody".to_string()))
+        );
+    }
+
+    #[test]
+    fn unmatched_braces_are_errors() {
+        assert_eq!(Template::parse("Hi, {user_name"), Err(TemplateError::Unclosed(4)));
+        assert_eq!(Template::parse("Hi, user_name}"), Err(TemplateError::Unopened(13)));
+    }
+
+    #[test]
+    fn placeholder_without_value_for_the_type_is_not_allowed() {
+        assert_eq!(
+            Template::validate("{percent} alike", &EntityTypes::MemeAlreadyExists),
+            Err(TemplateError::NotAllowed("percent"))
+        );
+        assert!(Template::validate("{percent} alike", &EntityTypes::SimilarMeme).is_ok());
+    }
+
+    #[test]
+    fn user_names_are_escaped() {
+        let user = teloxide::types::User {
+            id: teloxide::types::UserId(1),
+            is_bot: false,
+            first_name: "<b>Bob</b> & Co".to_string(),
+            last_name: None,
+            username: None,
+            language_code: None,
+            is_premium: false,
+            added_to_attachment_menu: false,
+        };
+        let mut values = TemplateValues::default();
+        values.set_html(Placeholder::UserName, crate::app::utils::get_user_text(&user));
+        values.set_text(Placeholder::AuthorName, "<i>Alice</i>");
+
+        let text = Template::parse("{user_name}, {author_name}").unwrap().render(&values);
+
+        assert_eq!(
+            text,
+            "<a href=\"tg://user/?id=1\">&lt;b&gt;Bob&lt;/b&gt; &amp; Co</a>, &lt;i&gt;Alice&lt;/i&gt;"
+        );
+    }
+}
@@ -1,55 +1,16 @@
-use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike, Utc, Weekday};
-use itertools::Itertools;
-use now::DateTimeNow;
-use std::str;
+use super::calendar::Calendar;
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, Utc};
 use teloxide::types::User;
+use teloxide::utils::html;
 
 pub fn get_user_text(user: &User) -> String {
     match &user.username {
         Some(uname) => format!("@{uname}"),
-        None => format!("<a href=\"{}\">{}</a>", user.url(), user.first_name),
+        None => format!("<a href=\"{}\">{}</a>", user.url(), html::escape(&user.first_name)),
     }
 }
 
-pub fn from_binary_to_hex(s: &str) -> String {
-    s.as_bytes()
-        .chunks(4)
-        .map(|c| {
-            format!(
-                "{:X}",
-                i8::from_str_radix(str::from_utf8(c).expect("Wrong char"), 2).unwrap()
-            )
-        })
-        .join("")
-}
-
-pub fn from_hex_to_binary(s: &str) -> String {
-    s.chars()
-        .map(|c| format!("{:04b}", i8::from_str_radix(&c.to_string(), 16).unwrap()))
-        .join("")
-}
-
-pub struct Messages {}
-
-impl Messages {
-    pub fn pluralize(num: i64, texts: (&str, &str, &str)) -> String {
-        let last = num % 100;
-
-        let plural = if (11..=19).contains(&last) {
-            texts.2.to_string()
-        } else {
-            match last % 10 {
-                1 => texts.0.to_string(),
-                2..=4 => texts.1.to_string(),
-                _ => texts.2.to_string(),
-            }
-        };
-
-        format!("{num} {plural}")
-    }
-}
-
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum Period {
     Week,
     Month,
@@ -58,81 +19,205 @@ pub enum Period {
 }
 
 impl Period {
+    /// Since the first meme of the bot
+    pub fn all_time() -> Self {
+        Period::Custom {
+            from: DateTime::UNIX_EPOCH,
+            to: Utc::now(),
+        }
+    }
+
     pub fn dates(&self) -> (DateTime<Utc>, DateTime<Utc>) {
-        match *self {
-            Period::Week => self.week_dates(),
-            Period::Month => self.month_dates(),
-            Period::Year => self.year_dates(),
-            Period::Custom { from, to } => (from, to),
+        self.dates_at(Calendar::global(), Utc::now())
+    }
+
+    /// Dates of the period which `now` belongs to.
+    /// Periods end at the cutoff of their last working day, the next one starts right then.
+    pub fn dates_at(&self, calendar: &Calendar, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        if let Period::Custom { from, to } = *self {
+            return (from, to);
         }
+
+        let (first, last) = self.days(calendar.date(now));
+        let mut from = self.end(calendar, first.pred_opt().unwrap());
+        let mut to = self.end(calendar, first);
+
+        if to <= now {
+            from = to;
+            to = self.end(calendar, last.succ_opt().unwrap());
+        }
+
+        (from, to - Duration::nanoseconds(1))
     }
 
-    pub fn is_today_a_friday() -> bool {
-        Weekday::Fri == Utc::now().weekday()
+    /// Dates of the latest period which is already over
+    pub fn last_dates(&self, calendar: &Calendar, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let (from, to) = self.dates_at(calendar, now);
+
+        if let Period::Custom { .. } = self {
+            return (from, to);
+        }
+
+        self.dates_at(calendar, from - Duration::nanoseconds(1))
     }
 
-    pub fn is_today_a_last_month_day() -> bool {
-        let now = Utc::now();
+    /// Name of the period in schedules and database
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Period::Week => "week",
+            Period::Month => "month",
+            Period::Year => "year",
+            Period::Custom { .. } => "custom",
+        }
+    }
 
-        if (now.month() != (now + Duration::try_days(3).unwrap()).month()) && Period::is_today_a_friday() {
+    /// Statistics of the period is sent today, when the period is over
+    pub fn is_due(&self, calendar: &Calendar, now: DateTime<Utc>) -> bool {
+        if let Period::Custom { .. } = self {
             return true;
         }
 
-        (now.end_of_month().day() == now.day()) && (Weekday::Sun != now.weekday()) && (Weekday::Sat != now.weekday())
+        let (_, to) = self.last_dates(calendar, now);
+
+        calendar.date(to) == calendar.date(now)
+    }
+
+    /// First and last days of the week, month or year of the date
+    fn days(&self, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match *self {
+            Period::Week => {
+                let first = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+
+                (first, first + Duration::days(6))
+            }
+            Period::Month => {
+                let first = date.with_day(1).unwrap();
+                let next = first.checked_add_months(Months::new(1)).unwrap();
+
+                (first, next.pred_opt().unwrap())
+            }
+            Period::Year => (
+                NaiveDate::from_ymd_opt(date.year(), 1, 1).unwrap(),
+                NaiveDate::from_ymd_opt(date.year(), 12, 31).unwrap(),
+            ),
+            Period::Custom { .. } => (date, date),
+        }
+    }
+
+    /// End of the week, month or year of the date
+    fn end(&self, calendar: &Calendar, date: NaiveDate) -> DateTime<Utc> {
+        let (first, last) = self.days(date);
+
+        calendar.cutoff(calendar.last_working_day(first, last))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::calendar::CalendarConfig;
+    use chrono::NaiveTime;
+    use chrono_tz::Tz;
+
+    fn calendar(timezone: Tz) -> Calendar {
+        Calendar::new(&CalendarConfig {
+            timezone,
+            cutoff: NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+            weekend: "Sat,Sun".parse().unwrap(),
+            holidays_file: None,
+        })
+        .unwrap()
     }
 
-    pub fn is_today_a_last_year_day() -> bool {
-        let now = Utc::now();
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
 
-        now.end_of_year().month() == now.month() && now.end_of_year().day() == now.day()
+    /// Periods end a nanosecond before the cutoff
+    fn period(from: &str, to: &str) -> (DateTime<Utc>, DateTime<Utc>) {
+        (at(from), at(to) - Duration::nanoseconds(1))
     }
 
-    fn from(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
-        (
-            Utc.with_ymd_and_hms(start.year(), start.month(), start.day(), 16, 0, 0)
-                .unwrap(),
-            Utc.with_ymd_and_hms(end.year(), end.month(), end.day(), 15, 59, 59)
-                .unwrap()
-                .with_nanosecond(999999999)
-                .unwrap(),
-        )
+    #[test]
+    fn year_changes_at_cutoff_of_31_december() {
+        let calendar = calendar(Tz::UTC);
+
+        assert_eq!(
+            Period::Year.dates_at(&calendar, at("2025-12-31T15:59:59Z")),
+            period("2024-12-31T16:00:00Z", "2025-12-31T16:00:00Z")
+        );
+        assert_eq!(
+            Period::Year.dates_at(&calendar, at("2025-12-31T16:00:00Z")),
+            period("2025-12-31T16:00:00Z", "2026-12-31T16:00:00Z")
+        );
+        assert_eq!(
+            Period::Year.dates_at(&calendar, at("2026-01-01T10:00:00Z")),
+            period("2025-12-31T16:00:00Z", "2026-12-31T16:00:00Z")
+        );
     }
 
-    fn week_dates(&self) -> (DateTime<Utc>, DateTime<Utc>) {
-        let start_week = Utc::now().beginning_of_week() + Duration::try_days(-3).unwrap();
-        let end_week = Utc::now().end_of_week() + Duration::try_days(-2).unwrap();
+    #[test]
+    fn year_is_due_on_31_december_only() {
+        let calendar = calendar(Tz::UTC);
 
-        self.from(start_week, end_week)
+        assert!(Period::Year.is_due(&calendar, at("2025-12-31T17:00:00Z")));
+        assert!(!Period::Year.is_due(&calendar, at("2026-01-01T10:00:00Z")));
+        assert_eq!(
+            Period::Year.last_dates(&calendar, at("2026-01-01T10:00:00Z")),
+            period("2024-12-31T16:00:00Z", "2025-12-31T16:00:00Z")
+        );
     }
 
-    fn month_dates(&self) -> (DateTime<Utc>, DateTime<Utc>) {
-        let start_month = self.get_first_work_day(&Utc::now().beginning_of_month());
-        let end_month = self.get_last_work_day(&Utc::now().end_of_month());
+    #[test]
+    fn february_ends_on_29_in_leap_years() {
+        let calendar = calendar(Tz::UTC);
 
-        self.from(start_month, end_month)
+        assert_eq!(
+            Period::Month.dates_at(&calendar, at("2024-02-29T12:00:00Z")),
+            period("2024-01-31T16:00:00Z", "2024-02-29T16:00:00Z")
+        );
+        assert!(Period::Month.is_due(&calendar, at("2024-02-29T17:00:00Z")));
+        assert!(!Period::Month.is_due(&calendar, at("2024-02-28T17:00:00Z")));
     }
 
-    fn year_dates(&self) -> (DateTime<Utc>, DateTime<Utc>) {
-        let start_year = self.get_first_work_day(&Utc::now().beginning_of_year());
-        let end_year = self.get_last_work_day(&Utc::now().end_of_year());
+    #[test]
+    fn february_ends_on_28_in_other_years() {
+        let calendar = calendar(Tz::UTC);
 
-        self.from(start_year, end_year)
+        assert_eq!(
+            Period::Month.dates_at(&calendar, at("2025-02-28T17:00:00Z")),
+            period("2025-02-28T16:00:00Z", "2025-03-31T16:00:00Z")
+        );
+        assert!(Period::Month.is_due(&calendar, at("2025-02-28T17:00:00Z")));
     }
 
-    fn get_last_work_day(&self, date: &DateTime<Utc>) -> DateTime<Utc> {
-        match date.weekday() {
-            Weekday::Sat => *date + Duration::try_days(-1).unwrap(),
-            Weekday::Sun => *date + Duration::try_days(-2).unwrap(),
-            _ => *date,
-        }
+    #[test]
+    fn february_29_on_saturday_moves_the_end_to_friday() {
+        let calendar = calendar(Tz::UTC);
+
+        assert_eq!(
+            Period::Month.last_dates(&calendar, at("2020-03-02T10:00:00Z")),
+            period("2020-01-31T16:00:00Z", "2020-02-28T16:00:00Z")
+        );
+        assert!(Period::Month.is_due(&calendar, at("2020-02-28T17:00:00Z")));
+        assert!(!Period::Month.is_due(&calendar, at("2020-02-29T17:00:00Z")));
     }
 
-    fn get_first_work_day(&self, date: &DateTime<Utc>) -> DateTime<Utc> {
-        match date.weekday() {
-            Weekday::Sat => *date + Duration::try_days(-1).unwrap(),
-            Weekday::Sun => *date + Duration::try_days(-2).unwrap(),
-            Weekday::Mon => *date + Duration::try_days(-3).unwrap(),
-            _ => *date,
-        }
+    #[test]
+    fn month_changes_at_local_cutoff_of_the_timezone() {
+        // 16:00 in New York is 21:00 UTC in winter
+        let calendar = calendar(Tz::America__New_York);
+
+        assert_eq!(
+            Period::Month.dates_at(&calendar, at("2026-01-30T20:59:59Z")),
+            period("2025-12-31T21:00:00Z", "2026-01-30T21:00:00Z")
+        );
+        assert_eq!(
+            Period::Month.dates_at(&calendar, at("2026-01-30T21:00:00Z")),
+            period("2026-01-30T21:00:00Z", "2026-02-27T21:00:00Z")
+        );
+        // It is still 30 January in New York
+        assert!(Period::Month.is_due(&calendar, at("2026-01-31T02:00:00Z")));
+        assert!(!Period::Month.is_due(&calendar, at("2026-01-31T06:00:00Z")));
     }
 }
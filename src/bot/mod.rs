@@ -12,12 +12,18 @@ use teloxide::{
     net::Download,
     prelude::*,
     types::{Chat, ParseMode, User},
+    utils::command::BotCommands,
 };
 use tokio::fs::File;
 
+use crate::app::locale::Language;
+use crate::redis::RedisManager;
+
 mod private;
 mod public;
+pub mod recap;
 pub mod statistics;
+pub mod templates;
 pub mod types;
 
 pub type Bot = DefaultParseMode<teloxide::Bot>;
@@ -27,23 +33,28 @@ pub static INSTANCE: OnceCell<BotManager> = OnceCell::new();
 
 #[derive(Envconfig, Clone, Debug)]
 pub struct BotConfig {
+    /// Legacy single chat. If set, it is registered on startup in addition to the chats from database.
     #[envconfig(from = "CHAT_ID")]
-    pub chat_id: i64,
+    pub chat_id: Option<i64>,
     #[envconfig(from = "BOT_TOKEN")]
     pub bot_token: String,
+    /// Telegram id of the user who manages stored messages of all chats
+    #[envconfig(from = "OWNER_ID")]
+    pub owner_id: Option<u64>,
+    /// Regular stickers are rated as memes only when enabled
+    #[envconfig(from = "REPOST_STICKERS", default = "false")]
+    pub repost_stickers: bool,
 }
 
 #[derive(Clone, Debug)]
 pub struct BotManager {
     bot: Bot,
-    chat_id: i64,
 }
 
 impl BotManager {
     pub fn new(config: &BotConfig) -> Self {
         Self {
             bot: teloxide::Bot::new(&config.bot_token).parse_mode(ParseMode::Html),
-            chat_id: config.chat_id,
         }
     }
 
@@ -51,14 +62,17 @@ impl BotManager {
         INSTANCE.get().expect("Can't get bot")
     }
 
-    pub async fn get_chat_user(&self, user_id: i64) -> User {
-        let member = self
-            .bot
-            .get_chat_member(ChatId(self.chat_id), UserId(user_id as u64))
+    /// `None` if the user is not in the chat anymore
+    pub async fn find_chat_user(&self, chat_id: i64, user_id: i64) -> Option<User> {
+        self.bot
+            .get_chat_member(ChatId(chat_id), UserId(user_id as u64))
             .await
-            .expect("Can't get chat member");
+            .ok()
+            .map(|member| member.user)
+    }
 
-        member.user
+    pub async fn get_chat(&self, chat_id: i64) -> Result<Chat> {
+        Ok(self.bot.get_chat(ChatId(chat_id)).await?)
     }
 
     pub async fn download_file(&self, file_id: &str) -> Result<String> {
@@ -95,8 +109,8 @@ impl BotManager {
         &self.bot
     }
 
-    fn filter_messages(ch: &Chat, chat_id: i64) -> bool {
-        (ch.is_group() || ch.is_supergroup()) && ch.id.0 == chat_id
+    fn filter_messages(ch: &Chat) -> bool {
+        (ch.is_group() || ch.is_supergroup()) && RedisManager::global().is_chat_registered(ch.id.0)
     }
 }
 
@@ -106,3 +120,17 @@ pub enum State {
     Idle,
     Private(private::PrivateState),
 }
+
+/// List of the commands with descriptions from the `command-<name>` texts
+pub fn commands_text<C: BotCommands>(language: Language) -> String {
+    let commands = C::bot_commands()
+        .iter()
+        .map(|c| {
+            let name = c.command.trim_start_matches('/').to_lowercase();
+
+            format!("{} — {}", c.command, language.text(&format!("command-{name}")))
+        })
+        .collect::<Vec<String>>();
+
+    format!("{}\n\n{}", language.text("commands-title"), commands.join("\n"))
+}
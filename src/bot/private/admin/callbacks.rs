@@ -1,20 +1,28 @@
-use super::types::CallbackOperations;
+use super::{templates, types::CallbackOperations};
+use crate::app::locale::Language;
+use crate::app::template::Template;
+use crate::app::Application;
 use crate::bot::{Bot, BotDialogue, State};
-use crate::database::entity::{messages::MessageTypes, prelude::Messages};
+use crate::database::entity::{
+    messages::{EntityTypes, MessageTypes},
+    prelude::{ChatAdmins, Chats, Messages},
+};
 use anyhow::Result;
-use teloxide::{payloads::AnswerCallbackQuerySetters, prelude::*};
+use std::sync::Arc;
+use teloxide::{payloads::AnswerCallbackQuerySetters, prelude::*, types::MessageEntityKind};
 
-pub async fn handle(bot: Bot, callback: CallbackQuery, dialogue: BotDialogue) -> Result<()> {
+pub async fn handle(bot: Bot, callback: CallbackQuery, dialogue: BotDialogue, app: Arc<Application>) -> Result<()> {
     let data: CallbackOperations = serde_json::from_str(&callback.data.clone().unwrap_or_else(|| r#"{}"#.to_string()))?;
 
     let msg = match callback.regular_message() {
         Some(m) => m.clone(),
         None => return Ok(()),
     };
+    let language = Language::of_user(&callback.from);
 
     if let CallbackOperations::Cancel = data {
         bot.answer_callback_query(callback.id)
-            .text("Галя, у нас отмена!")
+            .text(language.text("admin-add-cancelled"))
             .await?;
         dialogue.update(State::Idle).await?;
 
@@ -22,8 +30,30 @@ pub async fn handle(bot: Bot, callback: CallbackQuery, dialogue: BotDialogue) ->
         return Ok(());
     };
 
-    let (msg_type, text) = if let Some(t) = msg.text() {
-        (MessageTypes::Text, t.to_string())
+    if data.is_template_operation() {
+        if !templates::is_owner(&app, &callback.from) {
+            bot.answer_callback_query(callback.id)
+                .text(language.text("admin-templates-owner-only"))
+                .await?;
+
+            return Ok(());
+        }
+
+        return templates::callback_handle(bot, callback, msg, data, dialogue).await;
+    }
+
+    let entity_type: EntityTypes = data.into();
+    let (msg_type, text) = if let Some(t) = added_text(&msg) {
+        if let Err(e) = Template::validate(&t, &entity_type) {
+            bot.answer_callback_query(callback.id)
+                .text(language.format("admin-template-invalid", &[("error", &e)]))
+                .show_alert(true)
+                .await?;
+
+            return Ok(());
+        }
+
+        (MessageTypes::Text, t)
     } else if let Some(p) = msg.photo() {
         (MessageTypes::Photo, p[0].file.id.clone())
     } else if let Some(d) = msg.document() {
@@ -32,11 +62,37 @@ pub async fn handle(bot: Bot, callback: CallbackQuery, dialogue: BotDialogue) ->
         return Ok(());
     };
 
-    Messages::add(msg_type, data.into(), &text).await;
+    Messages::add(msg_type, entity_type, &text, chats_language(callback.from.id.0).await).await;
 
-    bot.answer_callback_query(callback.id).text("Добавил!").await?;
+    bot.answer_callback_query(callback.id)
+        .text(language.text("admin-added"))
+        .await?;
     bot.delete_message(msg.chat.id, msg.id).await?;
     dialogue.update(State::Idle).await?;
 
     Ok(())
 }
+
+/// The text is bold in the question about it
+fn added_text(msg: &Message) -> Option<String> {
+    msg.parse_entities()?
+        .into_iter()
+        .find(|entity| *entity.kind() == MessageEntityKind::Bold)
+        .map(|entity| entity.text().to_string())
+}
+
+/// Messages of the admin are sent to chats of the same language as the admin's ones, to all if they differ
+async fn chats_language(user_id: u64) -> Option<Language> {
+    let mut languages = Vec::new();
+
+    for chat_id in ChatAdmins::get_admin_chats(user_id).await {
+        languages.push(Chats::get_language(chat_id).await);
+    }
+
+    languages.dedup();
+
+    match languages[..] {
+        [language] => Some(language),
+        _ => None,
+    }
+}
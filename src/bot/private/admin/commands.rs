@@ -1,27 +1,46 @@
+use crate::app::locale::Language;
+use crate::app::template::Placeholder;
+use crate::app::utils::Period;
 use crate::app::Application;
-use crate::bot::{private::PrivateState, Bot, BotDialogue, State};
-use crate::database::entity::prelude::ChatAdmins;
+use crate::bot::statistics::{Output, Statistics};
+use crate::bot::{commands_text, private::PrivateState, Bot, BotDialogue, State};
+use crate::database::entity::prelude::{ChatAdmins, Chats};
 use std::sync::Arc;
-use teloxide::{prelude::*, utils::command::BotCommands};
+use teloxide::{
+    prelude::*,
+    utils::{command::BotCommands, html},
+};
 
 #[derive(BotCommands, Clone)]
-#[command(rename_rule = "lowercase", description = "Команды которые поддерживает бот:")]
+/// Descriptions are `command-<name>` texts of the admin language
+#[command(rename_rule = "lowercase")]
 pub enum AdminCommand {
-    #[command(description = "Показывает перечень команд")]
     Help,
-    #[command(description = "Отправить сообщение в чат")]
     Message(String),
-    #[command(description = "Добавить мем в базу")]
     AddMessage,
+    Preview(String),
+    Language(String),
+    Templates,
+}
+
+pub(super) fn language(msg: &Message) -> Language {
+    msg.from
+        .as_ref()
+        .map(Language::of_user)
+        .unwrap_or_else(Language::global)
 }
 
 pub async fn help_command(bot: Bot, msg: Message, app: Arc<Application>) -> anyhow::Result<()> {
+    let language = language(&msg);
+
     bot.send_message(
         msg.chat.id,
-        format!(
-            "{}\n\nВерсия бота: {}",
-            AdminCommand::descriptions(),
-            app.config.app_version
+        language.format(
+            "help-private",
+            &[
+                ("commands", &commands_text::<AdminCommand>(language)),
+                ("version", &app.config.app_version),
+            ],
         ),
     )
     .await?;
@@ -49,12 +68,146 @@ pub async fn message_command(bot: Bot, msg: Message, text: String) -> anyhow::Re
 }
 
 pub async fn add_message_command(bot: Bot, msg: Message, dialogue: BotDialogue) -> anyhow::Result<()> {
+    let language = language(&msg);
+    let placeholders = Placeholder::ALL
+        .iter()
+        .map(|p| format!("{{{}}}", p.name()))
+        .collect::<Vec<String>>()
+        .join(", ");
+
     bot.send_message(
         msg.chat.id,
-        "Отправьте изображение, текст или гифку для добавления ее в хранилище",
+        format!(
+            "{}\n\n{}",
+            language.text("admin-add-message"),
+            language.format("admin-placeholders", &[("placeholders", &placeholders)])
+        ),
     )
     .await?;
 
     dialogue.update(State::Private(PrivateState::AdminAddMessage)).await?;
     Ok(())
 }
+
+pub async fn preview_command(bot: Bot, msg: Message, args: String) -> anyhow::Result<()> {
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    let language = language(&msg);
+    let period = match args.trim() {
+        "" | "week" => Period::Week,
+        "month" => Period::Month,
+        "year" => Period::Year,
+        _ => {
+            bot.send_message(msg.chat.id, language.text("admin-preview-period"))
+                .await?;
+
+            return Ok(());
+        }
+    };
+    let statistics = Statistics::with_output(Output::Preview(msg.chat.id.0));
+
+    for chat_id in ChatAdmins::get_admin_chats(user.id.0).await {
+        let title = Chats::get_by_id(chat_id)
+            .await
+            .and_then(|c| c.title)
+            .map(|title| html::escape(&title))
+            .unwrap_or_else(|| chat_id.to_string());
+
+        bot.send_message(
+            msg.chat.id,
+            language.format("admin-preview-title", &[("title", &title)]),
+        )
+        .await?;
+        statistics.preview(chat_id, &period).await;
+    }
+
+    Ok(())
+}
+
+/// Sets the language of one of the chats of the admin: `/language ru|en|default [chat id or title]`.
+/// The chat may be omitted if the admin has only one.
+pub async fn language_command(bot: Bot, msg: Message, args: String) -> anyhow::Result<()> {
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    let language = language(&msg);
+    let (code, chat) = args.trim().split_once(char::is_whitespace).unwrap_or((args.trim(), ""));
+    let chat_language = match code {
+        "default" => None,
+        code => match code.parse::<Language>() {
+            Ok(chat_language) => Some(chat_language),
+            Err(_) => {
+                let text = if code.is_empty() {
+                    language.text("command-language")
+                } else {
+                    language.text("admin-language-wrong")
+                };
+
+                bot.send_message(msg.chat.id, text).await?;
+
+                return Ok(());
+            }
+        },
+    };
+
+    let mut chats = Vec::new();
+
+    for chat_id in ChatAdmins::get_admin_chats(user.id.0).await {
+        let title = Chats::get_by_id(chat_id).await.and_then(|c| c.title);
+
+        chats.push((chat_id, title));
+    }
+
+    let chat = chat.trim();
+    let found = chats
+        .iter()
+        .filter(|(chat_id, title)| {
+            chat.is_empty()
+                || chat == chat_id.to_string()
+                || title.as_deref().is_some_and(|t| t.eq_ignore_ascii_case(chat))
+        })
+        .collect::<Vec<_>>();
+
+    let [(chat_id, title)] = found[..] else {
+        let list = chats
+            .iter()
+            .map(|(chat_id, title)| {
+                let title = title.as_deref().map(html::escape).unwrap_or_default();
+
+                format!("<code>{chat_id}</code> {title}")
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        bot.send_message(
+            msg.chat.id,
+            language.format(
+                "admin-language-chat",
+                &[("code", &html::escape(code)), ("chats", &list)],
+            ),
+        )
+        .await?;
+
+        return Ok(());
+    };
+
+    if !Chats::set_language(*chat_id, chat_language).await {
+        warn!("Can't set language of chat {chat_id}");
+        return Ok(());
+    }
+
+    let title = title
+        .as_deref()
+        .map(html::escape)
+        .unwrap_or_else(|| chat_id.to_string());
+    let code = chat_language.unwrap_or_else(Language::global).code();
+
+    bot.send_message(
+        msg.chat.id,
+        language.format("admin-language-set", &[("title", &title), ("language", &code)]),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -1,9 +1,12 @@
+use crate::app::locale::Language;
+use crate::app::template::Template;
 use crate::bot::private::admin::types::CallbackOperations;
 use crate::bot::{Bot, BotDialogue, State};
 use serde_json::json;
 use teloxide::{
     prelude::*,
     types::{InlineKeyboardButton, InlineKeyboardMarkup, InputFile, MessageKind},
+    utils::html,
 };
 
 pub async fn add_message_handle(bot: Bot, msg: Message, dialogue: BotDialogue) -> anyhow::Result<()> {
@@ -14,26 +17,43 @@ pub async fn add_message_handle(bot: Bot, msg: Message, dialogue: BotDialogue) -
         _ => return Ok(()),
     }
 
+    let language = msg
+        .from
+        .as_ref()
+        .map(Language::of_user)
+        .unwrap_or_else(Language::global);
+
     if let Some(text) = msg.text() {
+        // Placeholders are checked again for the type of the message, when it is chosen
+        if let Err(e) = Template::parse(text) {
+            bot.send_message(
+                msg.chat.id,
+                language.format("admin-template-invalid", &[("error", &html::escape(&e.to_string()))]),
+            )
+            .await?;
+
+            return Ok(());
+        }
+
         bot.send_message(
             msg.chat.id,
-            format!("Вы хотите добавить этот текст:\n\n<b>{}</b>", text),
+            language.format("admin-add-text", &[("text", &html::escape(text))]),
         )
-        .reply_markup(get_markup())
+        .reply_markup(get_markup(language))
         .await?;
     }
 
     if let Some(photos) = msg.photo() {
         bot.send_photo(msg.chat.id, InputFile::file_id(&photos[0].file.id))
-            .caption("Вы хотите добавить это фото?")
-            .reply_markup(get_markup())
+            .caption(language.text("admin-add-photo"))
+            .reply_markup(get_markup(language))
             .await?;
     }
 
     if let Some(doc) = msg.document() {
         bot.send_document(msg.chat.id, InputFile::file_id(&doc.file.id))
-            .caption("Вы хотите добавить эту гифку?")
-            .reply_markup(get_markup())
+            .caption(language.text("admin-add-gif"))
+            .reply_markup(get_markup(language))
             .await?;
     }
 
@@ -42,26 +62,24 @@ pub async fn add_message_handle(bot: Bot, msg: Message, dialogue: BotDialogue) -
     Ok(())
 }
 
-fn get_markup() -> InlineKeyboardMarkup {
+fn get_markup(language: Language) -> InlineKeyboardMarkup {
+    let button = |key: &str, operation: CallbackOperations| {
+        InlineKeyboardButton::callback(language.text(key), json!(operation).to_string())
+    };
+
     InlineKeyboardMarkup::new(vec![
         vec![
-            InlineKeyboardButton::callback("Press F To Pray", json!(CallbackOperations::AddToFButton).to_string()),
-            InlineKeyboardButton::callback("User left", json!(CallbackOperations::AddToUserLeft).to_string()),
+            button("admin-type-press_f_to_pray_respects", CallbackOperations::AddToFButton),
+            button("admin-type-user_left_chat", CallbackOperations::AddToUserLeft),
         ],
         vec![
-            InlineKeyboardButton::callback(
-                "Meme already exists",
-                json!(CallbackOperations::AddToMemeAlreadyExists).to_string(),
+            button(
+                "admin-type-meme_already_exists",
+                CallbackOperations::AddToMemeAlreadyExists,
             ),
-            InlineKeyboardButton::callback("Newbie User", json!(CallbackOperations::AddToNewbieUser).to_string()),
+            button("admin-type-newbie_user", CallbackOperations::AddToNewbieUser),
         ],
-        vec![InlineKeyboardButton::callback(
-            "Similar Meme",
-            json!(CallbackOperations::AddToSimilarMeme).to_string(),
-        )],
-        vec![InlineKeyboardButton::callback(
-            "❌ Cancel",
-            json!(CallbackOperations::Cancel).to_string(),
-        )],
+        vec![button("admin-type-similar_meme", CallbackOperations::AddToSimilarMeme)],
+        vec![button("admin-add-cancel", CallbackOperations::Cancel)],
     ])
 }
@@ -12,6 +12,7 @@ use teloxide::{
 mod callbacks;
 mod commands;
 mod messages;
+mod templates;
 mod types;
 
 pub fn scheme() -> UpdateHandler<anyhow::Error> {
@@ -27,13 +28,21 @@ pub fn scheme() -> UpdateHandler<anyhow::Error> {
                         .branch(dptree::case![commands::AdminCommand::Message(x)].endpoint(commands::message_command))
                         .branch(
                             dptree::case![commands::AdminCommand::AddMessage].endpoint(commands::add_message_command),
+                        )
+                        .branch(dptree::case![commands::AdminCommand::Preview(x)].endpoint(commands::preview_command))
+                        .branch(dptree::case![commands::AdminCommand::Language(x)].endpoint(commands::language_command))
+                        .branch(
+                            dptree::case![commands::AdminCommand::Templates].endpoint(templates::templates_command),
                         ),
                 )
                 .branch(
                     Update::filter_message().branch(
-                        dptree::case![State::Private(x)].branch(
-                            dptree::case![PrivateState::AdminAddMessage].endpoint(messages::add_message_handle),
-                        ),
+                        dptree::case![State::Private(x)]
+                            .branch(dptree::case![PrivateState::AdminAddMessage].endpoint(messages::add_message_handle))
+                            .branch(
+                                dptree::case![PrivateState::AdminEditTemplate(x)]
+                                    .endpoint(templates::edit_template_handle),
+                            ),
                     ),
                 ),
         )
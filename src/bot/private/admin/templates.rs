@@ -0,0 +1,410 @@
+use super::{commands::language, types::CallbackOperations};
+use crate::app::locale::Language;
+use crate::app::template::Template;
+use crate::app::Application;
+use crate::bot::templates::{render, TemplateContext};
+use crate::bot::{private::PrivateState, Bot, BotDialogue, BotManager, State};
+use crate::database::entity::{
+    messages::{self, EntityTypes, MessageTypes},
+    prelude::{ChatAdmins, Memes, Messages},
+};
+use anyhow::Result;
+use sea_orm::{prelude::Uuid, ActiveEnum, Iterable};
+use serde_json::json;
+use std::sync::Arc;
+use teloxide::{
+    payloads::AnswerCallbackQuerySetters,
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, InputFile, User},
+    utils::html,
+};
+
+/// Heavier messages would take all the turns
+const MAX_WEIGHT: i32 = 100;
+
+/// Stored messages are shared by all chats, so only the owner of the bot manages them
+pub fn is_owner(app: &Application, user: &User) -> bool {
+    app.config.bot.owner_id == Some(user.id.0)
+}
+
+pub async fn templates_command(bot: Bot, msg: Message, app: Arc<Application>) -> Result<()> {
+    let language = language(&msg);
+
+    if !msg.from.as_ref().is_some_and(|user| is_owner(&app, user)) {
+        bot.send_message(msg.chat.id, language.text("admin-templates-owner-only"))
+            .await?;
+
+        return Ok(());
+    }
+
+    bot.send_message(msg.chat.id, language.text("admin-templates"))
+        .reply_markup(types_markup(language))
+        .await?;
+
+    Ok(())
+}
+
+pub async fn callback_handle(
+    bot: Bot,
+    callback: CallbackQuery,
+    msg: Message,
+    data: CallbackOperations,
+    dialogue: BotDialogue,
+) -> Result<()> {
+    let language = Language::of_user(&callback.from);
+
+    let uuid = match data {
+        CallbackOperations::TemplateTypes => {
+            bot.answer_callback_query(callback.id).await?;
+            bot.delete_message(msg.chat.id, msg.id).await?;
+            bot.send_message(msg.chat.id, language.text("admin-templates"))
+                .reply_markup(types_markup(language))
+                .await?;
+
+            return Ok(());
+        }
+        CallbackOperations::Templates(entity_type, page) => {
+            bot.answer_callback_query(callback.id).await?;
+            bot.delete_message(msg.chat.id, msg.id).await?;
+
+            return send_page(&bot, msg.chat.id, &callback.from, entity_type, page).await;
+        }
+        CallbackOperations::ToggleTemplate(uuid)
+        | CallbackOperations::WeighTemplate(uuid, _)
+        | CallbackOperations::EditTemplate(uuid)
+        | CallbackOperations::DeleteTemplate(uuid)
+        | CallbackOperations::RemoveTemplate(uuid) => uuid,
+        _ => unreachable!(),
+    };
+
+    let Some(template) = Messages::get_by_id(uuid).await else {
+        bot.answer_callback_query(callback.id)
+            .text(language.text("admin-template-gone"))
+            .await?;
+
+        return Ok(());
+    };
+    let page = page_of(&template).await;
+
+    match data {
+        CallbackOperations::ToggleTemplate(_) => {
+            if !template.set_enabled(!template.enabled).await {
+                warn!("Can't toggle message {uuid}");
+            }
+
+            bot.answer_callback_query(callback.id).await?;
+            bot.delete_message(msg.chat.id, msg.id).await?;
+            send_page(&bot, msg.chat.id, &callback.from, template.entity_type, page).await?;
+        }
+        CallbackOperations::WeighTemplate(_, weight) => {
+            if !template.set_weight(weight.clamp(1, MAX_WEIGHT)).await {
+                warn!("Can't set weight of message {uuid}");
+            }
+
+            bot.answer_callback_query(callback.id).await?;
+            bot.delete_message(msg.chat.id, msg.id).await?;
+            send_page(&bot, msg.chat.id, &callback.from, template.entity_type, page).await?;
+        }
+        CallbackOperations::EditTemplate(_) => {
+            if template.r#type != MessageTypes::Text {
+                bot.answer_callback_query(callback.id)
+                    .text(language.text("admin-template-edit-media"))
+                    .show_alert(true)
+                    .await?;
+
+                return Ok(());
+            }
+
+            bot.answer_callback_query(callback.id).await?;
+            bot.send_message(
+                msg.chat.id,
+                language.format(
+                    "admin-template-edit-text",
+                    &[("text", &html::escape(&template.message))],
+                ),
+            )
+            .reply_markup(InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
+                language.text("admin-template-cancel"),
+                json!(CallbackOperations::Cancel).to_string(),
+            )]]))
+            .await?;
+
+            dialogue
+                .update(State::Private(PrivateState::AdminEditTemplate(uuid)))
+                .await?;
+        }
+        CallbackOperations::DeleteTemplate(_) => {
+            bot.answer_callback_query(callback.id).await?;
+            bot.edit_message_reply_markup(msg.chat.id, msg.id)
+                .reply_markup(InlineKeyboardMarkup::new(vec![
+                    vec![InlineKeyboardButton::callback(
+                        language.text("admin-template-delete-confirm"),
+                        json!(CallbackOperations::RemoveTemplate(uuid)).to_string(),
+                    )],
+                    vec![InlineKeyboardButton::callback(
+                        language.text("admin-template-cancel"),
+                        json!(CallbackOperations::Templates(template.entity_type, page)).to_string(),
+                    )],
+                ]))
+                .await?;
+        }
+        CallbackOperations::RemoveTemplate(_) => {
+            if !template.remove().await {
+                warn!("Can't remove message {uuid}");
+            }
+
+            bot.answer_callback_query(callback.id)
+                .text(language.text("admin-template-deleted"))
+                .await?;
+            bot.delete_message(msg.chat.id, msg.id).await?;
+            // The next message takes the page of the removed one
+            send_page(&bot, msg.chat.id, &callback.from, template.entity_type, page).await?;
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+pub async fn edit_template_handle(bot: Bot, msg: Message, dialogue: BotDialogue, uuid: Uuid) -> Result<()> {
+    let language = language(&msg);
+
+    let Some(text) = msg.text() else {
+        bot.send_message(msg.chat.id, language.text("admin-template-send-text"))
+            .await?;
+
+        return Ok(());
+    };
+
+    let Some(template) = Messages::get_by_id(uuid).await else {
+        bot.send_message(msg.chat.id, language.text("admin-template-gone"))
+            .await?;
+        dialogue.update(State::Idle).await?;
+
+        return Ok(());
+    };
+
+    // The state is kept, so the admin can send a fixed text
+    if let Err(e) = Template::validate(text, &template.entity_type) {
+        bot.send_message(
+            msg.chat.id,
+            language.format("admin-template-invalid", &[("error", &html::escape(&e.to_string()))]),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    if !template.replace_message(text).await {
+        warn!("Can't edit message {uuid}");
+    }
+
+    dialogue.update(State::Idle).await?;
+    bot.send_message(msg.chat.id, language.text("admin-template-edited"))
+        .await?;
+
+    let page = page_of(&template).await;
+
+    send_page(
+        &bot,
+        msg.chat.id,
+        msg.from.as_ref().unwrap(),
+        template.entity_type,
+        page,
+    )
+    .await
+}
+
+/// Position of the message among the ones of its type
+async fn page_of(template: &messages::Model) -> usize {
+    Messages::get_by_entity_type(template.entity_type.clone())
+        .await
+        .iter()
+        .position(|m| m.uuid == template.uuid)
+        .unwrap_or_default()
+}
+
+/// Sends the message of the page as it appears in chats. Media can't replace a text in place, so pages are sent anew.
+async fn send_page(bot: &Bot, chat_id: ChatId, admin: &User, entity_type: EntityTypes, page: usize) -> Result<()> {
+    let language = Language::of_user(admin);
+    let templates = Messages::get_by_entity_type(entity_type.clone()).await;
+    let type_name = language.text(&format!("admin-type-{}", entity_type.to_value()));
+
+    // The last pages disappear when messages are removed
+    let page = page.min(templates.len().saturating_sub(1));
+
+    let Some(template) = templates.get(page) else {
+        bot.send_message(
+            chat_id,
+            language.format("admin-templates-empty", &[("type", &type_name)]),
+        )
+        .reply_markup(InlineKeyboardMarkup::new(vec![vec![types_button(language)]]))
+        .await?;
+
+        return Ok(());
+    };
+
+    let status = language.text(if template.enabled {
+        "admin-template-on"
+    } else {
+        "admin-template-off"
+    });
+    let template_language = template
+        .language
+        .clone()
+        .unwrap_or_else(|| language.text("admin-template-any-language"));
+    let caption = language.format(
+        "admin-template-caption",
+        &[
+            ("type", &type_name),
+            ("number", &(page + 1)),
+            ("count", &templates.len()),
+            ("language", &template_language),
+            ("weight", &template.weight),
+            ("status", &status),
+        ],
+    );
+    let markup = page_markup(template, page, templates.len(), language);
+
+    match template.r#type {
+        MessageTypes::Text => {
+            let preview = preview(template, admin).await;
+
+            bot.send_message(chat_id, format!("{caption}\n\n{preview}"))
+                .reply_markup(markup)
+                .await?;
+        }
+        MessageTypes::Photo => {
+            bot.send_photo(chat_id, InputFile::file_id(&template.message))
+                .caption(caption)
+                .reply_markup(markup)
+                .await?;
+        }
+        MessageTypes::Video => {
+            bot.send_video(chat_id, InputFile::file_id(&template.message))
+                .caption(caption)
+                .reply_markup(markup)
+                .await?;
+        }
+        MessageTypes::Document => {
+            bot.send_document(chat_id, InputFile::file_id(&template.message))
+                .caption(caption)
+                .reply_markup(markup)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// HTML of the text filled with the admin, one of their chats and a meme of it
+async fn preview(template: &messages::Model, admin: &User) -> String {
+    let parsed = match Template::parse(&template.message) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            return format!(
+                "{}\n\n⚠️ {}",
+                html::escape(&template.message),
+                html::escape(&e.to_string())
+            )
+        }
+    };
+
+    let chat = match ChatAdmins::get_admin_chats(admin.id.0).await.first() {
+        Some(chat_id) => BotManager::global().get_chat(*chat_id).await.ok(),
+        None => None,
+    };
+    let meme = match &chat {
+        Some(chat) => Memes::get_page(Some(chat.id.0), None, 1).await.pop(),
+        None => None,
+    };
+    let context = TemplateContext {
+        chat: chat.as_ref(),
+        user: Some(admin),
+        meme: meme.as_ref(),
+        percent: Some(95),
+    };
+    let language = template
+        .language
+        .as_deref()
+        .and_then(|l| l.parse().ok())
+        .unwrap_or_else(Language::global);
+
+    render(&parsed, language, &context).await
+}
+
+fn types_markup(language: Language) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(EntityTypes::iter().map(|entity_type| {
+        vec![InlineKeyboardButton::callback(
+            language.text(&format!("admin-type-{}", entity_type.to_value())),
+            json!(CallbackOperations::Templates(entity_type, 0)).to_string(),
+        )]
+    }))
+}
+
+fn types_button(language: Language) -> InlineKeyboardButton {
+    InlineKeyboardButton::callback(
+        language.text("admin-template-types"),
+        json!(CallbackOperations::TemplateTypes).to_string(),
+    )
+}
+
+fn page_markup(template: &messages::Model, page: usize, pages: usize, language: Language) -> InlineKeyboardMarkup {
+    let mut navigation = Vec::new();
+
+    if page > 0 {
+        navigation.push(InlineKeyboardButton::callback(
+            language.text("admin-template-previous"),
+            json!(CallbackOperations::Templates(template.entity_type.clone(), page - 1)).to_string(),
+        ));
+    }
+
+    if page + 1 < pages {
+        navigation.push(InlineKeyboardButton::callback(
+            language.text("admin-template-next"),
+            json!(CallbackOperations::Templates(template.entity_type.clone(), page + 1)).to_string(),
+        ));
+    }
+
+    let weights = vec![
+        InlineKeyboardButton::callback(
+            language.text("admin-template-lighter"),
+            json!(CallbackOperations::WeighTemplate(
+                template.uuid,
+                (template.weight - 1).max(1)
+            ))
+            .to_string(),
+        ),
+        InlineKeyboardButton::callback(
+            language.text("admin-template-heavier"),
+            json!(CallbackOperations::WeighTemplate(
+                template.uuid,
+                (template.weight + 1).min(MAX_WEIGHT)
+            ))
+            .to_string(),
+        ),
+    ];
+
+    let mut actions = vec![InlineKeyboardButton::callback(
+        language.text(if template.enabled {
+            "admin-template-disable"
+        } else {
+            "admin-template-enable"
+        }),
+        json!(CallbackOperations::ToggleTemplate(template.uuid)).to_string(),
+    )];
+
+    if template.r#type == MessageTypes::Text {
+        actions.push(InlineKeyboardButton::callback(
+            language.text("admin-template-edit"),
+            json!(CallbackOperations::EditTemplate(template.uuid)).to_string(),
+        ));
+    }
+
+    actions.push(InlineKeyboardButton::callback(
+        language.text("admin-template-delete"),
+        json!(CallbackOperations::DeleteTemplate(template.uuid)).to_string(),
+    ));
+
+    InlineKeyboardMarkup::new(vec![navigation, weights, actions, vec![types_button(language)]])
+}
@@ -1,6 +1,8 @@
 use crate::database::entity::messages::EntityTypes;
+use sea_orm::prelude::Uuid;
 use serde::{Deserialize, Serialize};
 
+/// Telegram limits callback data to 64 bytes, so names are short enough to fit a uuid
 #[derive(Serialize, Deserialize, Debug)]
 pub enum CallbackOperations {
     AddToFButton,
@@ -9,6 +11,31 @@ pub enum CallbackOperations {
     AddToSimilarMeme,
     AddToMemeAlreadyExists,
     Cancel,
+    TemplateTypes,
+    /// Page of the stored messages of the type, one message per page
+    Templates(EntityTypes, usize),
+    ToggleTemplate(Uuid),
+    /// Sets the weight of the message
+    WeighTemplate(Uuid, i32),
+    EditTemplate(Uuid),
+    /// Asks to confirm the removal
+    DeleteTemplate(Uuid),
+    RemoveTemplate(Uuid),
+}
+
+impl CallbackOperations {
+    pub fn is_template_operation(&self) -> bool {
+        matches!(
+            self,
+            CallbackOperations::TemplateTypes
+                | CallbackOperations::Templates(..)
+                | CallbackOperations::ToggleTemplate(_)
+                | CallbackOperations::WeighTemplate(..)
+                | CallbackOperations::EditTemplate(_)
+                | CallbackOperations::DeleteTemplate(_)
+                | CallbackOperations::RemoveTemplate(_)
+        )
+    }
 }
 
 impl From<CallbackOperations> for EntityTypes {
@@ -1,25 +1,95 @@
+use crate::app::locale::Language;
 use crate::app::Application;
-use crate::bot::Bot;
+use crate::bot::{commands_text, recap, Bot};
+use crate::database::entity::prelude::Users;
+use chrono::{Datelike, Utc};
 use std::sync::Arc;
 use teloxide::{prelude::*, utils::command::BotCommands};
 
 #[derive(BotCommands, Clone)]
-#[command(rename_rule = "lowercase", description = "Команды которые поддерживает бот:")]
+/// Descriptions are `command-<name>` texts of the user language
+#[command(rename_rule = "lowercase")]
 pub enum PrivateCommand {
-    #[command(description = "Показывает перечень команд")]
     Help,
+    Recap(String),
 }
 
 pub async fn help_command(bot: Bot, msg: Message, app: Arc<Application>) -> anyhow::Result<()> {
+    let language = msg
+        .from
+        .as_ref()
+        .map(Language::of_user)
+        .unwrap_or_else(Language::global);
+
     bot.send_message(
         msg.chat.id,
-        format!(
-            "{}\n\nВерсия бота: {}",
-            PrivateCommand::descriptions(),
-            app.config.app_version
+        language.format(
+            "help-private",
+            &[
+                ("commands", &commands_text::<PrivateCommand>(language)),
+                ("version", &app.config.app_version),
+            ],
         ),
     )
     .await?;
 
     Ok(())
 }
+
+pub async fn recap_command(bot: Bot, msg: Message, args: String) -> anyhow::Result<()> {
+    let Some(user) = msg.from.clone() else {
+        return Ok(());
+    };
+    let user_id = user.id.0 as i64;
+    let language = Language::of_user(&user);
+    let args = args.trim();
+
+    if let "on" | "off" = args {
+        let recap = args == "on";
+        let key = if Users::add(user.into()).await && Users::set_recap(user_id, recap).await {
+            if recap {
+                "recap-on"
+            } else {
+                "recap-off"
+            }
+        } else {
+            "recap-failed"
+        };
+
+        bot.send_message(msg.chat.id, language.text(key)).await?;
+
+        return Ok(());
+    }
+
+    if !Users::get_by_id(user_id).await.is_some_and(|u| u.recap) {
+        bot.send_message(msg.chat.id, language.text("recap-disabled")).await?;
+
+        return Ok(());
+    }
+
+    let year = if args.is_empty() {
+        Utc::now().year()
+    } else {
+        match args.parse::<i32>() {
+            Ok(year) => year,
+            Err(_) => {
+                bot.send_message(msg.chat.id, language.text("recap-wrong-year")).await?;
+
+                return Ok(());
+            }
+        }
+    };
+
+    let text = match recap::year_dates(year) {
+        Some(dates) => recap::recap_text(user_id, year, dates, language).await,
+        None => None,
+    };
+
+    bot.send_message(
+        msg.chat.id,
+        text.unwrap_or_else(|| language.format("recap-empty", &[("year", &year)])),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use commands::PrivateCommand;
+use sea_orm::prelude::Uuid;
 use serde::{Deserialize, Serialize};
 use teloxide::{
     dispatching::{UpdateFilterExt, UpdateHandler},
@@ -17,7 +18,8 @@ pub fn scheme() -> UpdateHandler<anyhow::Error> {
             .branch(
                 Update::filter_message()
                     .filter_command::<PrivateCommand>()
-                    .branch(dptree::case![PrivateCommand::Help].endpoint(commands::help_command)),
+                    .branch(dptree::case![PrivateCommand::Help].endpoint(commands::help_command))
+                    .branch(dptree::case![PrivateCommand::Recap(args)].endpoint(commands::recap_command)),
             )
             .branch(Update::filter_message().endpoint(messages::handle)),
     )
@@ -28,4 +30,6 @@ pub enum PrivateState {
     #[default]
     Idle,
     AdminAddMessage,
+    /// Waits for the new text of the stored message
+    AdminEditTemplate(Uuid),
 }
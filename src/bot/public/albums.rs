@@ -0,0 +1,176 @@
+use super::markups::*;
+use super::messages::{meme_already_exists, photo_handle, similar_meme, video_handle};
+use crate::app::{Application, SimilarMeme};
+use crate::bot::{types::AlbumItem, Bot};
+use crate::database::entity::prelude::{Chats, MemeHashes, Memes};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use teloxide::{
+    payloads::SendMessageSetters,
+    prelude::*,
+    types::{InputFile, InputMedia, InputMediaPhoto, InputMediaVideo, ParseMode, ReplyParameters},
+};
+
+/// Telegram sends album photos and videos as separate updates, so they are collected for a while
+const ALBUM_WAIT: Duration = Duration::from_millis(1500);
+
+/// Chat id and media group id
+type AlbumKey = (i64, String);
+
+static ALBUMS: Lazy<Mutex<HashMap<AlbumKey, Vec<Message>>>> = Lazy::new(Default::default);
+
+pub fn buffer(bot: Bot, msg: Message, app: Arc<Application>) {
+    let Some(group_id) = msg.media_group_id() else {
+        return;
+    };
+    let key = (msg.chat.id.0, group_id.to_string());
+    let mut albums = ALBUMS.lock().unwrap();
+
+    if let Some(messages) = albums.get_mut(&key) {
+        messages.push(msg);
+        return;
+    }
+
+    albums.insert(key.clone(), vec![msg]);
+
+    // Updates of one chat are handled sequentially, so waiting for the rest must not block the dispatcher
+    tokio::spawn(async move {
+        tokio::time::sleep(ALBUM_WAIT).await;
+
+        let messages = ALBUMS.lock().unwrap().remove(&key).unwrap_or_default();
+
+        if let Err(e) = album_handle(&bot, messages, &app).await {
+            error!("Can't handle album: {e}");
+        }
+    });
+}
+
+async fn album_handle(bot: &Bot, mut messages: Vec<Message>, app: &Application) -> anyhow::Result<()> {
+    messages.sort_by_key(|m| m.id.0);
+
+    let Some(first) = messages.first() else {
+        return Ok(());
+    };
+
+    if messages.len() == 1 {
+        return match first.video() {
+            Some(_) => video_handle(bot, first, app).await,
+            None => photo_handle(bot, first, app).await,
+        };
+    }
+
+    let user_text = crate::app::utils::get_user_text(first.from.as_ref().unwrap());
+    let mut hashes = Vec::with_capacity(messages.len());
+    // The most similar item of the album decides
+    let mut s_meme = SimilarMeme::default();
+
+    for msg in &messages {
+        let (item_hashes, found) = match AlbumItem::of(msg) {
+            Some(AlbumItem::Photo(photos)) => match app.generate_hashes(&photos[0].file.id).await {
+                Ok(h) => {
+                    let found = app.get_similar_meme(first.chat.id.0, &h).await;
+
+                    (vec![h], found)
+                }
+                Err(e) => {
+                    warn!("Can't generate hashes for album photo. Error: {e}");
+                    continue;
+                }
+            },
+            Some(AlbumItem::Video(video)) => match app.generate_video_hashes(&video.file.id).await {
+                Ok(frames) if !frames.is_empty() => {
+                    let found = app.get_similar_video(first.chat.id.0, &frames).await;
+
+                    (frames, found)
+                }
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!("Can't generate hashes for album video. Error: {e}");
+                    continue;
+                }
+            },
+            None => continue,
+        };
+
+        hashes.extend(item_hashes);
+
+        if found.percent > s_meme.percent {
+            s_meme = found;
+        }
+    }
+
+    for msg in &messages {
+        if let Err(e) = bot.delete_message(msg.chat.id, msg.id).await {
+            warn!("Can't delete album message {} in chat {}: {e}", msg.id.0, msg.chat.id.0);
+        }
+    }
+
+    if s_meme.percent == 100 {
+        return meme_already_exists(bot, first, &s_meme.meme.unwrap()).await;
+    }
+
+    let meme = match Memes::add_album(&messages).await {
+        None => {
+            warn!("Meme is empty after insert!");
+            return Ok(());
+        }
+        Some(m) => m,
+    };
+
+    MemeHashes::add(meme.uuid, &hashes).await;
+    app.index.insert(&meme, &hashes).await;
+
+    let language = Chats::get_language(first.chat.id.0).await;
+    let caption = match messages.iter().find_map(|m| m.caption()) {
+        Some(caption) => language.format("meme-caption", &[("caption", &caption)]),
+        None => String::new(),
+    };
+
+    let rate = language.format("rate-album", &[("user", &user_text), ("caption", &caption)]);
+    let media = messages.iter().filter_map(AlbumItem::of).enumerate().map(|(i, item)| {
+        // Telegram shows the caption of the first item as the caption of the whole album
+        let caption = if i == 0 { Some(rate.clone()) } else { None };
+
+        match item {
+            AlbumItem::Photo(photos) => {
+                let mut photo = InputMediaPhoto::new(InputFile::file_id(&photos[0].file.id));
+
+                if let Some(caption) = caption {
+                    photo = photo.caption(caption).parse_mode(ParseMode::Html);
+                }
+
+                InputMedia::Photo(photo)
+            }
+            AlbumItem::Video(video) => {
+                let mut input = InputMediaVideo::new(InputFile::file_id(&video.file.id));
+
+                if let Some(caption) = caption {
+                    input = input.caption(caption).parse_mode(ParseMode::Html);
+                }
+
+                InputMedia::Video(input)
+            }
+        }
+    });
+
+    let album = bot.send_media_group(first.chat.id, media).await?;
+    let markup = MemeMarkup::new(0, 0, meme.uuid, language);
+
+    // Media groups can't have a keyboard, so votes go to a separate message
+    let bot_msg = bot
+        .send_message(
+            first.chat.id,
+            language.format("rate-album-votes", &[("user", &user_text)]),
+        )
+        .reply_parameters(ReplyParameters::new(album[0].id))
+        .reply_markup(markup.get_markup())
+        .await?;
+
+    meme.replace_msg_id(bot_msg.id.0 as i64).await;
+    meme.replace_album_msg_ids(&album.iter().map(|m| m.id.0 as i64).collect::<Vec<_>>())
+        .await;
+
+    similar_meme(bot, first, &meme, &s_meme).await
+}
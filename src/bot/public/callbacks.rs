@@ -4,15 +4,20 @@ use anyhow::Result;
 use teloxide::dispatching::dialogue::GetChatId;
 use teloxide::prelude::*;
 
+use super::complaints::{self, Verdict};
 use super::markups::*;
 use super::types::*;
+use crate::app::locale::Language;
 use crate::app::Application;
 use crate::bot::Bot;
-use crate::database::entity::{meme_likes::MemeLikesCountAll, memes::Model as MemeModel, prelude::Memes};
+use crate::database::entity::{
+    meme_likes::MemeLikesCountAll,
+    memes::Model as MemeModel,
+    prelude::{Chats, MemeComplaints, Memes},
+};
 use crate::redis::RedisManager;
 
 pub struct CallbackHandler {
-    #[allow(dead_code)]
     pub app: Arc<Application>,
     pub bot: Bot,
     pub callback: CallbackQuery,
@@ -24,7 +29,20 @@ impl CallbackHandler {
         let data: MemeCallback =
             serde_json::from_str(&handler.callback.data.clone().unwrap_or_else(|| r#"{}"#.to_string()))?;
 
-        let meme = Memes::get_by_id(data.uuid).await.expect("Can't get meme from callback");
+        let Some(meme) = Memes::get_by_id(data.uuid).await else {
+            let language = match handler.callback.chat_id() {
+                Some(chat_id) => Chats::get_language(chat_id.0).await,
+                None => Language::global(),
+            };
+
+            handler
+                .bot
+                .answer_callback_query(&handler.callback.id)
+                .text(language.text("callback-meme-gone"))
+                .await?;
+
+            return Ok(());
+        };
 
         match data.op {
             CallbackOperations::Like => {
@@ -39,8 +57,27 @@ impl CallbackHandler {
             CallbackOperations::None => {
                 handler.none(&meme).await?;
             }
+            CallbackOperations::Complain => {
+                handler.complain(&meme).await?;
+            }
+        };
+
+        Ok(())
+    }
+
+    pub async fn top_handle(bot: Bot, callback: CallbackQuery, data: TopCallback) -> Result<()> {
+        let msg = match callback.regular_message() {
+            Some(msg) => msg,
+            None => return Ok(()),
         };
 
+        let (text, markup) = super::commands::top_page(msg.chat.id.0, &data).await;
+
+        bot.edit_message_text(msg.chat.id, msg.id, text)
+            .reply_markup(markup)
+            .await?;
+        bot.answer_callback_query(&callback.id).await?;
+
         Ok(())
     }
 
@@ -95,11 +132,12 @@ impl CallbackHandler {
             Some(msg) => msg,
             None => return Ok(()),
         };
+        let language = Chats::get_language(meme.chat_id).await;
 
         if !self.can_user_interact(meme) {
             self.bot
                 .answer_callback_query(&self.callback.id)
-                .text("Только тот, кто прислал мем (или админ), может сделать это")
+                .text(language.text("callback-not-allowed"))
                 .show_alert(true)
                 .await?;
 
@@ -110,7 +148,7 @@ impl CallbackHandler {
 
         self.bot
             .answer_callback_query(&self.callback.id)
-            .text("Штош, на Вашей совести")
+            .text(language.text("callback-kept"))
             .await?;
 
         Ok(())
@@ -121,32 +159,100 @@ impl CallbackHandler {
             Some(msg) => msg,
             None => return Ok(()),
         };
+        let language = Chats::get_language(meme.chat_id).await;
 
         if !self.can_user_interact(meme) {
             self.bot
                 .answer_callback_query(&self.callback.id)
-                .text("Только тот, кто прислал мем (или админ), может сделать это")
+                .text(language.text("callback-not-allowed"))
                 .show_alert(true)
                 .await?;
 
             return Ok(());
         }
 
-        self.bot.delete_message(msg.chat.id, msg.id).await?;
-        self.bot.delete_message(meme.chat_id(), meme.msg_id()).await?;
+        if let Err(e) = self.bot.delete_message(msg.chat.id, msg.id).await {
+            warn!("Can't delete message {} in chat {}: {e}", msg.id.0, msg.chat.id.0);
+        }
 
-        meme.remove().await;
+        // Messages deleted by someone else must not keep the meme in statistics
+        for msg_id in std::iter::once(meme.msg_id()).chain(meme.album_msg_ids()) {
+            if let Err(e) = self.bot.delete_message(meme.chat_id(), msg_id).await {
+                warn!("Can't delete message {} of meme {}: {e}", msg_id.0, meme.uuid);
+            }
+        }
+
+        if !meme.remove().await {
+            warn!("Can't remove meme {}", meme.uuid);
+        }
 
         self.bot
             .answer_callback_query(&self.callback.id)
-            .text("УдОлено")
+            .text(language.text("callback-deleted"))
             .await?;
 
         Ok(())
     }
 
+    /// Members complain or take the complaint back, enough of them decide the meme is a repost
+    pub async fn complain(&self, meme: &MemeModel) -> Result<()> {
+        let msg = match self.callback.regular_message() {
+            Some(msg) => msg,
+            None => return Ok(()),
+        };
+        let language = Chats::get_language(meme.chat_id).await;
+
+        // Complaints don't change anything once the meme is decided to be a repost
+        if meme.reposted_at.is_some() {
+            self.bot
+                .answer_callback_query(&self.callback.id)
+                .text(language.text("callback-already-reposted"))
+                .await?;
+
+            return Ok(());
+        }
+
+        let user_id = self.callback.from.id.0 as i64;
+        let withdrawn = MemeComplaints::exists(meme.uuid, user_id).await;
+
+        if withdrawn {
+            MemeComplaints::remove(meme.uuid, user_id).await;
+        } else {
+            MemeComplaints::add(meme.uuid, user_id).await;
+        }
+
+        let config = &self.app.config.accordion;
+
+        match complaints::judge(&self.bot, meme, config).await {
+            Verdict::Pending(count) => {
+                self.bot
+                    .edit_message_reply_markup(msg.chat.id, msg.id)
+                    .reply_markup(complaints::markup(meme, count, config, language))
+                    .await?;
+                self.bot
+                    .answer_callback_query(&self.callback.id)
+                    .text(language.text(if withdrawn {
+                        "callback-complaint-withdrawn"
+                    } else {
+                        "callback-complained"
+                    }))
+                    .await?;
+            }
+            verdict => {
+                if let Some(text) = verdict.announcement(language) {
+                    self.bot.edit_message_text(msg.chat.id, msg.id, text).await?;
+                }
+
+                self.bot.answer_callback_query(&self.callback.id).await?;
+            }
+        }
+
+        Ok(())
+    }
+
     async fn update_message(&self, meme: &MemeModel, msg: &Message, counts: MemeLikesCountAll) -> Result<()> {
-        let meme_markup = MemeMarkup::new(counts.likes, counts.dislikes, meme.uuid);
+        let language = Chats::get_language(meme.chat_id).await;
+        let meme_markup = MemeMarkup::new(counts.likes, counts.dislikes, meme.uuid, language);
 
         self.bot
             .edit_message_reply_markup(msg.chat.id, msg.id)
@@ -155,7 +261,7 @@ impl CallbackHandler {
 
         self.bot
             .answer_callback_query(&self.callback.id)
-            .text("Круто, что тебе не пофиг")
+            .text(language.text("callback-voted"))
             .await?;
 
         Ok(())
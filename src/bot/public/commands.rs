@@ -1,9 +1,13 @@
-use super::markups::DeleteMarkup;
+use super::complaints::{self, Verdict};
+use super::markups::{DeleteMarkup, TopMarkup};
+use super::types::{TopCallback, TopPeriod};
+use crate::app::utils::{get_user_text, Period};
 use crate::app::Application;
-use crate::bot::Bot;
+use crate::bot::{commands_text, Bot, BotManager};
 use crate::database::entity::{
     messages::EntityTypes,
-    prelude::{MemeLikes, Memes, Messages},
+    prelude::{Chats, MemeComplaints, MemeLikes, Memes, Messages, Users},
+    users::Leaderboard,
 };
 use crate::redis::RedisManager;
 use std::sync::Arc;
@@ -11,25 +15,27 @@ use teloxide::types::ReplyParameters;
 use teloxide::{
     payloads::SendMessageSetters,
     prelude::{Message, Requester},
-    types::InputFile,
-    utils::command::BotCommands,
+    types::{InlineKeyboardMarkup, InputFile, User},
+    utils::{command::BotCommands, html},
 };
 
 #[derive(BotCommands, Clone)]
-#[command(rename_rule = "lowercase", description = "Команды которые поддерживает бот:")]
+/// Descriptions are `command-<name>` texts of the chat language
+#[command(rename_rule = "lowercase")]
 pub enum PublicCommand {
-    #[command(description = "Показывает перечень команд")]
     Help,
-    #[command(rename_rule = "UPPERCASE", description = "Press \"F\" to Pray Respects")]
+    #[command(rename_rule = "UPPERCASE")]
     F,
-    #[command(description = "Это даже не баян, это аккордеон на**й")]
     Accordion,
-    #[command(description = "Удалить свой мем")]
     UnMeme,
-    #[command(description = "Статистика мемочата")]
     Stats,
+    Me,
+    Top(String),
 }
 
+/// Places on one page of `/top`
+const TOP_PAGE_SIZE: usize = 10;
+
 pub async fn help_command(bot: Bot, msg: Message, app: Arc<Application>) -> anyhow::Result<()> {
     let can_send = RedisManager::global().can_send_message("help", msg.chat.id.0, msg.id.0);
     bot.delete_message(msg.chat.id, msg.id).await?;
@@ -38,30 +44,35 @@ pub async fn help_command(bot: Bot, msg: Message, app: Arc<Application>) -> anyh
         return Ok(());
     }
 
-    bot
-        .send_message(
-            msg.chat.id,
-            format!(
-                "{}\n\nЧтобы бот не посчитал твое сообщение мемом, достаточно указать в тексте сообщения к картинке <b>nomem</b>\nIssue\\Предложения: <a href=\"https://github.com/mediclab/telegram_meme_bot/issues\">писать сюда</a>\nВерсия бота: {}",
-                PublicCommand::descriptions(),
-                app.config.app_version
-            ),
-        )
-        // .disable_web_page_preview(true)
-        .await?;
+    let language = Chats::get_language(msg.chat.id.0).await;
+
+    bot.send_message(
+        msg.chat.id,
+        language.format(
+            "help-public",
+            &[
+                ("commands", &commands_text::<PublicCommand>(language)),
+                ("version", &app.config.app_version),
+            ],
+        ),
+    )
+    // .disable_web_page_preview(true)
+    .await?;
 
     Ok(())
 }
 
 pub async fn f_command(bot: Bot, msg: Message) -> anyhow::Result<()> {
-    let photo_id = Messages::get_random_photo(EntityTypes::PressFToPrayRespects).await;
+    let language = Chats::get_language(msg.chat.id.0).await;
+    let photo_id = Messages::get_random_photo(EntityTypes::PressFToPrayRespects, language, Some(msg.chat.id.0)).await;
     bot.send_photo(msg.chat.id, InputFile::file_id(&photo_id)).await?;
 
     Ok(())
 }
 
-pub async fn accordion_command(bot: Bot, msg: Message) -> anyhow::Result<()> {
+pub async fn accordion_command(bot: Bot, msg: Message, app: Arc<Application>) -> anyhow::Result<()> {
     let me = bot.get_me().await?;
+    let language = Chats::get_language(msg.chat.id.0).await;
     bot.delete_message(msg.chat.id, msg.id).await?;
 
     match msg.reply_to_message() {
@@ -70,12 +81,6 @@ pub async fn accordion_command(bot: Bot, msg: Message) -> anyhow::Result<()> {
                 return Ok(());
             }
 
-            let can_send = RedisManager::global().can_send_message("accordion", msg.chat.id.0, msg.id.0);
-
-            if !can_send {
-                return Ok(());
-            }
-
             let meme = match Memes::get_by_msg_id(repl.chat.id.0, repl.id.0 as u64).await {
                 None => {
                     warn!("Meme not found by msg_id: {}!", repl.id.0);
@@ -84,35 +89,49 @@ pub async fn accordion_command(bot: Bot, msg: Message) -> anyhow::Result<()> {
                 }
                 Some(m) => m,
             };
-            let user_res = bot.get_chat_member(msg.chat.id, meme.user_id()).await;
-            let mut user_text = String::new();
 
-            if user_res.is_ok() {
-                user_text = format!("{}!\n", crate::app::utils::get_user_text(&user_res.unwrap().user));
+            // The complaint counts even if the question about the meme is not sent again
+            if !MemeComplaints::add(meme.uuid, msg.from.as_ref().unwrap().id.0 as i64).await {
+                warn!("Can't add complaint about meme {}", meme.uuid);
+            }
+
+            let verdict = complaints::judge(&bot, &meme, &app.config.accordion).await;
+            let can_send = RedisManager::global().can_send_message("accordion", msg.chat.id.0, msg.id.0);
+
+            if !can_send {
+                return Ok(());
             }
 
-            bot.send_message(
-                msg.chat.id,
-                format!("{user_text} Пользователи жалуются на великое баянище!\nЧто будем с ним делать?"),
-            )
-            .reply_parameters(ReplyParameters::new(repl.id))
-            .reply_markup(
-                DeleteMarkup::new(meme.uuid)
-                    .set_ok_text("👎 Удалите, прошу прощения")
-                    .set_none_text("👍 Беру на себя ответственность")
-                    .get_markup(),
-            )
-            .await?;
+            match verdict {
+                Verdict::Pending(count) => {
+                    let user_res = bot.get_chat_member(msg.chat.id, meme.user_id()).await;
+                    let mut user_text = String::new();
+
+                    if let Ok(member) = user_res {
+                        user_text = format!("{}!\n", crate::app::utils::get_user_text(&member.user));
+                    }
+
+                    bot.send_message(
+                        msg.chat.id,
+                        language.format("accordion-complaint", &[("user", &user_text)]),
+                    )
+                    .reply_parameters(ReplyParameters::new(repl.id))
+                    .reply_markup(complaints::markup(&meme, count, &app.config.accordion, language))
+                    .await?;
+                }
+                verdict => {
+                    if let Some(text) = verdict.announcement(language) {
+                        bot.send_message(msg.chat.id, text).await?;
+                    }
+                }
+            }
         }
         None => {
             let can_send = RedisManager::global().can_send_message("accordion_none", msg.chat.id.0, msg.id.0);
 
             if can_send {
-                bot.send_message(
-                    msg.chat.id,
-                    String::from("Чтобы пожаловаться на сообщение, на него нужно ответить!"),
-                )
-                .await?;
+                bot.send_message(msg.chat.id, language.text("accordion-no-reply"))
+                    .await?;
             }
         }
     }
@@ -122,6 +141,7 @@ pub async fn accordion_command(bot: Bot, msg: Message) -> anyhow::Result<()> {
 
 pub async fn unmeme_command(bot: Bot, msg: Message) -> anyhow::Result<()> {
     let me = bot.get_me().await?;
+    let language = Chats::get_language(msg.chat.id.0).await;
     bot.delete_message(msg.chat.id, msg.id).await?;
 
     match msg.reply_to_message() {
@@ -145,12 +165,12 @@ pub async fn unmeme_command(bot: Bot, msg: Message) -> anyhow::Result<()> {
                 Some(m) => m,
             };
 
-            bot.send_message(msg.chat.id, String::from("Ты хочешь удалить мем?"))
+            bot.send_message(msg.chat.id, language.text("unmeme-question"))
                 .reply_parameters(ReplyParameters::new(repl.id))
                 .reply_markup(
                     DeleteMarkup::new(meme.uuid)
-                        .set_ok_text("🗑 Да, я хочу удалить")
-                        .set_none_text("❌ Нет, я передумал(а)")
+                        .set_ok_text(&language.text("unmeme-delete"))
+                        .set_none_text(&language.text("unmeme-keep"))
                         .get_markup(),
                 )
                 .await?;
@@ -158,11 +178,7 @@ pub async fn unmeme_command(bot: Bot, msg: Message) -> anyhow::Result<()> {
         None => {
             let can_send = RedisManager::global().can_send_message("unmeme_none", msg.chat.id.0, msg.id.0);
             if can_send {
-                bot.send_message(
-                    msg.chat.id,
-                    String::from("Чтобы удалить свой мем, нужно ответить на него!"),
-                )
-                .await?;
+                bot.send_message(msg.chat.id, language.text("unmeme-no-reply")).await?;
             }
         }
     }
@@ -171,6 +187,18 @@ pub async fn unmeme_command(bot: Bot, msg: Message) -> anyhow::Result<()> {
 }
 
 pub async fn stats_command(bot: Bot, msg: Message) -> anyhow::Result<()> {
+    if let Some(reply) = msg.reply_to_message() {
+        // Memes are reposted by the bot, so the author is the one who sent the meme
+        let user = match Memes::get_by_msg_id(msg.chat.id.0, reply.id.0 as u64).await {
+            Some(meme) => BotManager::global().find_chat_user(meme.chat_id, meme.user_id).await,
+            None => reply.from.clone(),
+        };
+
+        if let Some(user) = user.filter(|u| !u.is_bot) {
+            return user_stats(&bot, &msg, &user).await;
+        }
+    }
+
     let can_send = RedisManager::global().can_send_message("stats", msg.chat.id.0, msg.id.0);
     bot.delete_message(msg.chat.id, msg.id).await?;
 
@@ -182,24 +210,168 @@ pub async fn stats_command(bot: Bot, msg: Message) -> anyhow::Result<()> {
     let mut likes_count = 0;
     let mut dislikes_count = 0;
 
-    if let Some(like_counts) = MemeLikes::count_all(None).await {
+    if let Some(like_counts) = MemeLikes::count_all_by_chat(msg.chat.id.0).await {
         likes_count = like_counts.likes;
         dislikes_count = like_counts.dislikes;
     }
 
-    let mut message = "<b>Статистика мемочата (за все время):</b>
+    let message = Chats::get_language(msg.chat.id.0).await.format(
+        "stats-chat",
+        &[
+            ("memes", &memes_count),
+            ("likes", &likes_count),
+            ("dislikes", &dislikes_count),
+        ],
+    );
 
-🤡 Всего отправлено мемов: {memes_count}
-❤️ Всего поставлено лайков: {memes_likes}
-💔 Всего поставлено дизлайков: {memes_dislikes}"
-        .to_owned();
+    bot.send_message(msg.chat.id, message).await?;
 
-    message = message
-        .replace("{memes_count}", &memes_count.to_string())
-        .replace("{memes_likes}", &likes_count.to_string())
-        .replace("{memes_dislikes}", &dislikes_count.to_string());
+    Ok(())
+}
+
+pub async fn me_command(bot: Bot, msg: Message) -> anyhow::Result<()> {
+    match msg.from.as_ref() {
+        Some(user) => user_stats(&bot, &msg, user).await,
+        None => Ok(()),
+    }
+}
+
+async fn user_stats(bot: &Bot, msg: &Message, user: &User) -> anyhow::Result<()> {
+    let can_send = RedisManager::global().can_send_message(&format!("stats_{}", user.id.0), msg.chat.id.0, msg.id.0);
+    bot.delete_message(msg.chat.id, msg.id).await?;
+
+    if !can_send {
+        return Ok(());
+    }
+
+    let user_id = user.id.0 as i64;
+    let language = Chats::get_language(msg.chat.id.0).await;
+    let (from, to) = Period::all_time().dates();
+    let stats = Users::get_stats(msg.chat.id.0, user_id, from, to).await;
+
+    let mut message = language.format(
+        "stats-user",
+        &[
+            ("user", &get_user_text(user)),
+            ("memes", &stats.memes),
+            ("likes", &stats.likes),
+            ("dislikes", &stats.dislikes),
+            ("likes_given", &stats.likes_given),
+            ("dislikes_given", &stats.dislikes_given),
+            ("selflikes", &language.plural("times", stats.selflikes)),
+        ],
+    );
+
+    if let Some(rank) = stats.rank {
+        message.push_str(&format!("\n{}", language.format("stats-rank", &[("rank", &rank)])));
+    }
+
+    if let Some(meme) = Memes::get_max_liked_of_user(msg.chat.id.0, user_id, from, to).await {
+        let likes = meme.count_all_likes().await.map(|c| c.likes).unwrap_or_default();
+        let likes = language.plural("likes", likes);
+
+        match Message::url_of(msg.chat.id, msg.chat.username(), meme.msg_id()) {
+            Some(url) => message.push_str(&format!(
+                "\n{}",
+                language.format("best-meme-link", &[("url", &url), ("likes", &likes)])
+            )),
+            None => message.push_str(&format!("\n{}", language.format("best-meme", &[("likes", &likes)]))),
+        }
+    }
 
     bot.send_message(msg.chat.id, message).await?;
 
     Ok(())
 }
+
+pub async fn top_command(bot: Bot, msg: Message, args: String) -> anyhow::Result<()> {
+    let can_send = RedisManager::global().can_send_message("top", msg.chat.id.0, msg.id.0);
+    bot.delete_message(msg.chat.id, msg.id).await?;
+
+    if !can_send {
+        return Ok(());
+    }
+
+    let mut callback = TopCallback {
+        top: Leaderboard::Liked,
+        period: TopPeriod::Month,
+        page: 0,
+    };
+
+    for arg in args.split_whitespace() {
+        match arg.to_lowercase().as_str() {
+            "week" => callback.period = TopPeriod::Week,
+            "month" => callback.period = TopPeriod::Month,
+            "year" => callback.period = TopPeriod::Year,
+            "all" => callback.period = TopPeriod::All,
+            "memes" => callback.top = Leaderboard::Memesenders,
+            "likes" => callback.top = Leaderboard::Liked,
+            "dislikes" => callback.top = Leaderboard::Disliked,
+            _ => {}
+        }
+    }
+
+    let (text, markup) = top_page(msg.chat.id.0, &callback).await;
+
+    bot.send_message(msg.chat.id, text).reply_markup(markup).await?;
+
+    Ok(())
+}
+
+/// Text and buttons of the leaderboard page
+pub async fn top_page(chat_id: i64, callback: &TopCallback) -> (String, InlineKeyboardMarkup) {
+    let language = Chats::get_language(chat_id).await;
+    let (from, to) = callback.period.period().dates();
+    let leaderboard = Users::leaderboard(chat_id, callback.top, from, to).await;
+    let pages = leaderboard.len().div_ceil(TOP_PAGE_SIZE).max(1);
+    let page = callback.page.min(pages - 1);
+
+    let counts = match callback.top {
+        Leaderboard::Memesenders => "memes",
+        Leaderboard::Disliked => "dislikes",
+        _ => "likes",
+    };
+    let period = match callback.period {
+        TopPeriod::Week => "week",
+        TopPeriod::Month => "month",
+        TopPeriod::Year => "year",
+        TopPeriod::All => "all",
+    };
+
+    let mut lines = Vec::new();
+
+    for user in leaderboard.iter().skip(page * TOP_PAGE_SIZE).take(TOP_PAGE_SIZE) {
+        let name = match BotManager::global().find_chat_user(chat_id, user.user_id).await {
+            Some(tg_user) => get_user_text(&tg_user),
+            None => Users::get_by_id(user.user_id)
+                .await
+                .map(|u| html::escape(&u.firstname))
+                .unwrap_or_else(|| language.text("top-unknown")),
+        };
+
+        lines.push(format!(
+            "{}. {name} — {}",
+            user.rank,
+            language.plural(counts, user.count)
+        ));
+    }
+
+    if lines.is_empty() {
+        lines.push(language.text("top-empty"));
+    }
+
+    let title = language.format(
+        "top-title",
+        &[
+            ("top", &language.text(&format!("top-by-{counts}"))),
+            ("period", &language.text(&format!("top-period-{period}"))),
+            ("page", &(page + 1)),
+            ("pages", &pages),
+        ],
+    );
+
+    (
+        format!("{title}\n\n{}", lines.join("\n")),
+        TopMarkup::new(TopCallback { page, ..*callback }, pages, language).get_markup(),
+    )
+}
@@ -0,0 +1,80 @@
+use super::markups::DeleteMarkup;
+use crate::app::locale::Language;
+use crate::app::{AccordionAction, AccordionConfig};
+use crate::bot::Bot;
+use crate::database::entity::{memes, prelude::MemeComplaints};
+use chrono::{Duration, Utc};
+use teloxide::{prelude::*, types::InlineKeyboardMarkup};
+
+/// What the complaints of members decided about a meme
+pub enum Verdict {
+    /// Not enough members complained yet, with the count of the ones who did
+    Pending(u64),
+    Removed,
+    Reposted,
+}
+
+impl Verdict {
+    /// Text for the chat when the meme is decided to be a repost
+    pub fn announcement(&self, language: Language) -> Option<String> {
+        match *self {
+            Verdict::Pending(_) => None,
+            Verdict::Removed => Some(language.text("accordion-removed")),
+            Verdict::Reposted => Some(language.text("accordion-reposted")),
+        }
+    }
+}
+
+/// Removes the meme or marks it as a repost, when enough members complained in the window
+pub async fn judge(bot: &Bot, meme: &memes::Model, config: &AccordionConfig) -> Verdict {
+    if meme.reposted_at.is_some() {
+        return Verdict::Reposted;
+    }
+
+    let since = Utc::now().naive_utc() - Duration::hours(config.window_hours);
+    let complaints = MemeComplaints::count_since(meme.uuid, since).await;
+
+    if complaints < config.votes {
+        return Verdict::Pending(complaints);
+    }
+
+    match config.action {
+        AccordionAction::Remove => {
+            // Messages deleted by someone else must not keep the meme in statistics
+            for msg_id in std::iter::once(meme.msg_id()).chain(meme.album_msg_ids()) {
+                if let Err(e) = bot.delete_message(meme.chat_id(), msg_id).await {
+                    warn!("Can't delete message {} of meme {}: {e}", msg_id.0, meme.uuid);
+                }
+            }
+
+            if !meme.remove().await {
+                warn!("Can't remove meme {}", meme.uuid);
+            }
+
+            Verdict::Removed
+        }
+        AccordionAction::MarkRepost => {
+            if !meme.mark_reposted().await {
+                warn!("Can't mark meme {} as repost", meme.uuid);
+            }
+
+            Verdict::Reposted
+        }
+    }
+}
+
+pub fn markup(
+    meme: &memes::Model,
+    complaints: u64,
+    config: &AccordionConfig,
+    language: Language,
+) -> InlineKeyboardMarkup {
+    DeleteMarkup::new(meme.uuid)
+        .set_complaint_text(&language.format(
+            "accordion-complain",
+            &[("count", &complaints), ("votes", &config.votes)],
+        ))
+        .set_ok_text(&language.text("accordion-delete"))
+        .set_none_text(&language.text("accordion-keep"))
+        .get_markup()
+}
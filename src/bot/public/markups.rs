@@ -1,4 +1,5 @@
 use super::types::*;
+use crate::app::locale::Language;
 use serde_json::json;
 use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
 use uuid::Uuid;
@@ -7,17 +8,23 @@ pub struct MemeMarkup {
     likes: i64,
     dislikes: i64,
     uuid: Uuid,
+    language: Language,
 }
 
 impl MemeMarkup {
-    pub fn new(likes: i64, dislikes: i64, uuid: Uuid) -> Self {
-        Self { likes, dislikes, uuid }
+    pub fn new(likes: i64, dislikes: i64, uuid: Uuid, language: Language) -> Self {
+        Self {
+            likes,
+            dislikes,
+            uuid,
+            language,
+        }
     }
 
     pub fn get_markup(&self) -> InlineKeyboardMarkup {
         InlineKeyboardMarkup::new(vec![vec![
             InlineKeyboardButton::callback(
-                format!("❤️ Like ({})", self.likes),
+                self.language.format("rate-like", &[("count", &self.likes)]),
                 json!(MemeCallback {
                     uuid: self.uuid,
                     op: CallbackOperations::Like
@@ -25,7 +32,7 @@ impl MemeMarkup {
                 .to_string(),
             ),
             InlineKeyboardButton::callback(
-                format!("💔 Dislike ({})", self.dislikes),
+                self.language.format("rate-dislike", &[("count", &self.dislikes)]),
                 json!(MemeCallback {
                     uuid: self.uuid,
                     op: CallbackOperations::Dislike
@@ -40,6 +47,7 @@ pub struct DeleteMarkup {
     uuid: Uuid,
     ok_text: Option<String>,
     none_text: Option<String>,
+    complaint_text: Option<String>,
 }
 
 impl DeleteMarkup {
@@ -48,6 +56,7 @@ impl DeleteMarkup {
             uuid,
             ok_text: None,
             none_text: None,
+            complaint_text: None,
         }
     }
 
@@ -61,8 +70,27 @@ impl DeleteMarkup {
         self
     }
 
+    /// Adds the button for members to complain, without it only the author and admins decide
+    pub fn set_complaint_text(mut self, text: &str) -> Self {
+        self.complaint_text = Some(text.to_string());
+        self
+    }
+
     pub fn get_markup(&self) -> InlineKeyboardMarkup {
-        InlineKeyboardMarkup::new(vec![
+        let mut buttons = Vec::new();
+
+        if let Some(text) = &self.complaint_text {
+            buttons.push(vec![InlineKeyboardButton::callback(
+                text,
+                json!(MemeCallback {
+                    uuid: self.uuid,
+                    op: CallbackOperations::Complain
+                })
+                .to_string(),
+            )]);
+        }
+
+        buttons.extend([
             vec![InlineKeyboardButton::callback(
                 self.none_text.to_owned().unwrap_or(String::from("None")),
                 json!(MemeCallback {
@@ -79,6 +107,52 @@ impl DeleteMarkup {
                 })
                 .to_string(),
             )],
-        ])
+        ]);
+
+        InlineKeyboardMarkup::new(buttons)
+    }
+}
+
+pub struct TopMarkup {
+    callback: TopCallback,
+    pages: usize,
+    language: Language,
+}
+
+impl TopMarkup {
+    pub fn new(callback: TopCallback, pages: usize, language: Language) -> Self {
+        Self {
+            callback,
+            pages,
+            language,
+        }
+    }
+
+    pub fn get_markup(&self) -> InlineKeyboardMarkup {
+        let mut buttons = Vec::new();
+
+        if self.callback.page > 0 {
+            buttons.push(InlineKeyboardButton::callback(
+                self.language.text("top-back"),
+                json!(TopCallback {
+                    page: self.callback.page - 1,
+                    ..self.callback
+                })
+                .to_string(),
+            ));
+        }
+
+        if self.callback.page + 1 < self.pages {
+            buttons.push(InlineKeyboardButton::callback(
+                self.language.text("top-next"),
+                json!(TopCallback {
+                    page: self.callback.page + 1,
+                    ..self.callback
+                })
+                .to_string(),
+            ));
+        }
+
+        InlineKeyboardMarkup::new(vec![buttons])
     }
 }
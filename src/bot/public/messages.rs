@@ -1,39 +1,40 @@
 use super::markups::*;
-use crate::app::Application;
+use crate::app::{imghash::ImageHashes, Application, SimilarMeme};
+use crate::bot::templates::{self, TemplateContext};
 use crate::bot::Bot;
 use crate::database::entity::{
+    memes::Model as MemeModel,
     messages::EntityTypes,
-    prelude::{Memes, Messages, Users},
+    prelude::{Chats, MemeHashes, Memes, Users},
 };
 use crate::redis::RedisManager;
 use std::sync::Arc;
 use teloxide::types::{MessageKind, ReplyParameters};
 use teloxide::{
-    payloads::{SendMessageSetters, SendPhotoSetters},
+    payloads::{SendAnimationSetters, SendMessageSetters, SendPhotoSetters, SendStickerSetters, SendVideoSetters},
     prelude::*,
-    types::{ChatMemberKind, InputFile},
+    types::{Animation, ChatMemberKind, InputFile, PhotoSize, Sticker, Video},
 };
 
 pub async fn chat_member_handle(bot: Bot, cm: ChatMemberUpdated) -> anyhow::Result<()> {
     let member = cm.new_chat_member;
+    let language = Chats::get_language(cm.chat.id.0).await;
+    let context = TemplateContext {
+        chat: Some(&cm.chat),
+        user: Some(&member.user),
+        ..Default::default()
+    };
+
     match member.kind {
         ChatMemberKind::Member => {
-            let message = Messages::get_random_text(EntityTypes::NewbieUser).await;
-            bot.send_message(
-                cm.chat.id,
-                message.replace("{user_name}", &crate::app::utils::get_user_text(&member.user)),
-            )
-            .await?;
+            let message = templates::random_text(EntityTypes::NewbieUser, language, &context).await;
+            bot.send_message(cm.chat.id, message).await?;
 
             Users::add(member.user.into()).await;
         }
         ChatMemberKind::Left | ChatMemberKind::Banned(_) => {
-            let message = Messages::get_random_text(EntityTypes::UserLeftChat).await;
-            bot.send_message(
-                cm.chat.id,
-                message.replace("{user_name}", &crate::app::utils::get_user_text(&member.user)),
-            )
-            .await?;
+            let message = templates::random_text(EntityTypes::UserLeftChat, language, &context).await;
+            bot.send_message(cm.chat.id, message).await?;
 
             Users::delete(member.user.id.0 as i64).await;
         }
@@ -43,6 +44,22 @@ pub async fn chat_member_handle(bot: Bot, cm: ChatMemberUpdated) -> anyhow::Resu
     Ok(())
 }
 
+pub async fn my_chat_member_handle(cm: ChatMemberUpdated, app: Arc<Application>) -> anyhow::Result<()> {
+    if !(cm.chat.is_group() || cm.chat.is_supergroup()) {
+        return Ok(());
+    }
+
+    let member = cm.new_chat_member;
+
+    if member.is_present() {
+        app.register_chat(&cm.chat).await;
+    } else {
+        app.unregister_chat(cm.chat.id.0).await;
+    }
+
+    Ok(())
+}
+
 pub async fn common(bot: Bot, msg: Message, app: Arc<Application>) -> anyhow::Result<()> {
     match msg.kind {
         MessageKind::Common(_) => {
@@ -70,60 +87,155 @@ pub async fn common(bot: Bot, msg: Message, app: Arc<Application>) -> anyhow::Re
         return Ok(());
     }
 
-    if msg.photo().is_some() || msg.video().is_some() {
+    if msg.photo().is_some() || msg.video().is_some() || msg.animation().is_some() || msg.sticker().is_some() {
         if !RedisManager::global().is_chat_registered(msg.chat.id.0) {
             warn!("Chat {} is not registered", msg.chat.id.0);
 
             return Ok(());
         }
 
+        if msg.media_group_id().is_some() && (msg.photo().is_some() || msg.video().is_some()) {
+            super::albums::buffer(bot, msg, app);
+
+            return Ok(());
+        }
+
         if msg.photo().is_some() {
             photo_handle(&bot, &msg, &app).await?;
         }
 
         if msg.video().is_some() {
-            video_handle(&bot, &msg).await?
+            video_handle(&bot, &msg, &app).await?
+        }
+
+        if msg.animation().is_some() {
+            animation_handle(&bot, &msg, &app).await?
+        }
+
+        if msg.sticker().is_some() {
+            sticker_handle(&bot, &msg, &app).await?
         }
     }
 
     Ok(())
 }
 
-async fn photo_handle(bot: &Bot, msg: &Message, app: &Application) -> anyhow::Result<()> {
-    let user = msg.from.as_ref().unwrap();
-    let photos = if let Some(photos) = msg.photo() {
-        photos
+pub(super) async fn photo_handle(bot: &Bot, msg: &Message, app: &Application) -> anyhow::Result<()> {
+    let Some(photos) = msg.photo() else {
+        return Ok(());
+    };
+
+    let hashes = match app.generate_hashes(&photos[0].file.id).await {
+        Ok(hashes) => hashes,
+        Err(e) => {
+            warn!("Can't generate hashes. Error: {e}");
+            return Ok(());
+        }
+    };
+
+    let s_meme = app.get_similar_meme(msg.chat.id.0, &hashes).await;
+
+    repost(bot, msg, app, Media::Photo(&photos[0]), vec![hashes], s_meme).await
+}
+
+pub(super) async fn video_handle(bot: &Bot, msg: &Message, app: &Application) -> anyhow::Result<()> {
+    let Some(video) = msg.video() else {
+        return Ok(());
+    };
+
+    if already_posted(bot, msg, &video.file.unique_id).await? {
+        return Ok(());
+    }
+
+    // Big videos can't be downloaded by bots, they are checked only by file_unique_id
+    let frames = app.generate_video_hashes(&video.file.id).await.unwrap_or_else(|e| {
+        warn!("Can't generate video hashes. Error: {e}");
+        Vec::new()
+    });
+
+    let s_meme = if frames.is_empty() {
+        SimilarMeme::default()
     } else {
+        app.get_similar_video(msg.chat.id.0, &frames).await
+    };
+
+    repost(bot, msg, app, Media::Video(video), frames, s_meme).await
+}
+
+async fn animation_handle(bot: &Bot, msg: &Message, app: &Application) -> anyhow::Result<()> {
+    let Some(animation) = msg.animation() else {
         return Ok(());
     };
-    let user_text = crate::app::utils::get_user_text(user);
 
-    let hash_result = app.generate_hashes(&photos[0].file.id).await;
-    let (Some(hash), Some(hash_min)) = hash_result.unwrap_or_else(|e| {
-        warn!("Can't generate hashes. Error: {e}");
+    if already_posted(bot, msg, &animation.file.unique_id).await? {
+        return Ok(());
+    }
 
-        (None, None)
-    }) else {
-        error!("Hashes incorrect");
+    let (hashes, s_meme) = thumbnail_similar(app, msg, animation.thumbnail.as_ref()).await;
+
+    repost(bot, msg, app, Media::Animation(animation), hashes, s_meme).await
+}
+
+async fn sticker_handle(bot: &Bot, msg: &Message, app: &Application) -> anyhow::Result<()> {
+    if !app.config.bot.repost_stickers {
         return Ok(());
+    }
+
+    let sticker = match msg.sticker() {
+        // Animated stickers are vector drawings, they can't be hashed or sent as something else
+        Some(sticker) if sticker.is_regular() && !sticker.is_animated() => sticker,
+        _ => return Ok(()),
     };
 
-    let s_meme = Application::get_similar_meme(&hash_min, &hash).await;
+    if already_posted(bot, msg, &sticker.file.unique_id).await? {
+        return Ok(());
+    }
+
+    let (hashes, s_meme) = thumbnail_similar(app, msg, sticker.thumbnail.as_ref()).await;
+
+    repost(bot, msg, app, Media::Sticker(sticker), hashes, s_meme).await
+}
+
+/// Media of a single message which is re-sent as a meme
+enum Media<'a> {
+    Photo(&'a PhotoSize),
+    Video(&'a Video),
+    Animation(&'a Animation),
+    Sticker(&'a Sticker),
+}
+
+/// Replies about the meme with the same file, the message is deleted then
+async fn already_posted(bot: &Bot, msg: &Message, file_unique_id: &str) -> anyhow::Result<bool> {
+    let Some(meme) = Memes::get_by_file_unique_id(msg.chat.id.0, file_unique_id).await else {
+        return Ok(false);
+    };
 
     bot.delete_message(msg.chat.id, msg.id).await?;
+    meme_already_exists(bot, msg, &meme).await?;
 
-    if s_meme.percent == 100 {
-        let meme = s_meme.meme.unwrap();
-        let message = Messages::get_random_text(EntityTypes::MemeAlreadyExists).await;
+    Ok(true)
+}
 
-        bot.send_message(msg.chat.id, message.replace("{user_name}", &user_text))
-            .reply_parameters(ReplyParameters::new(meme.msg_id()))
-            .await?;
+/// Deletes the message of the user and sends its media again with the rating keyboard
+async fn repost(
+    bot: &Bot,
+    msg: &Message,
+    app: &Application,
+    media: Media<'_>,
+    hashes: Vec<ImageHashes>,
+    s_meme: SimilarMeme,
+) -> anyhow::Result<()> {
+    bot.delete_message(msg.chat.id, msg.id).await?;
 
-        return Ok(());
+    if s_meme.percent == 100 {
+        return meme_already_exists(bot, msg, &s_meme.meme.unwrap()).await;
     }
 
-    let meme = match Memes::add(msg, &Some(hash), &Some(hash_min)).await {
+    // Frames of videos are stored apart from the meme
+    let is_video = matches!(media, Media::Video(_));
+    let meme_hashes = if is_video { None } else { hashes.first() };
+
+    let meme = match Memes::add(msg, meme_hashes).await {
         None => {
             warn!("Meme is empty after insert!");
             return Ok(());
@@ -131,77 +243,126 @@ async fn photo_handle(bot: &Bot, msg: &Message, app: &Application) -> anyhow::Re
         Some(m) => m,
     };
 
-    let markup = MemeMarkup::new(0, 0, meme.uuid);
-    let caption = if let Some(caption) = msg.caption() {
-        format!("\n\nС подписью: {caption}")
-    } else {
-        String::new()
-    };
+    if is_video {
+        MemeHashes::add(meme.uuid, &hashes).await;
+    }
 
-    let bot_msg = bot
-        .send_photo(msg.chat.id, InputFile::file_id(&photos[0].file.id))
-        .caption(format!("Оцените мем {user_text}{caption}"))
-        .reply_markup(markup.get_markup())
-        .await?;
+    if !hashes.is_empty() {
+        app.index.insert(&meme, &hashes).await;
+    }
+
+    let user_text = crate::app::utils::get_user_text(msg.from.as_ref().unwrap());
+    let language = Chats::get_language(msg.chat.id.0).await;
+    let markup = MemeMarkup::new(0, 0, meme.uuid, language).get_markup();
+    let caption = match msg.caption() {
+        Some(caption) => language.format("meme-caption", &[("caption", &caption)]),
+        None => String::new(),
+    };
+    let rate = |key: &str| language.format(key, &[("user", &user_text), ("caption", &caption)]);
+
+    let bot_msg = match media {
+        Media::Photo(photo) => {
+            bot.send_photo(msg.chat.id, InputFile::file_id(&photo.file.id))
+                .caption(rate("rate-photo"))
+                .reply_markup(markup)
+                .await?
+        }
+        Media::Video(video) => {
+            bot.send_video(msg.chat.id, InputFile::file_id(&video.file.id))
+                .caption(rate("rate-video"))
+                .reply_markup(markup)
+                .await?
+        }
+        Media::Animation(animation) => {
+            bot.send_animation(msg.chat.id, InputFile::file_id(&animation.file.id))
+                .caption(rate("rate-animation"))
+                .reply_markup(markup)
+                .await?
+        }
+        // Stickers can't have a caption, the keyboard is attached directly
+        Media::Sticker(sticker) => {
+            bot.send_sticker(msg.chat.id, InputFile::file_id(&sticker.file.id))
+                .reply_markup(markup)
+                .await?
+        }
+    };
 
     meme.replace_msg_id(bot_msg.id.0 as i64).await;
 
-    if s_meme.percent > 0 {
-        let message = Messages::get_random_text(EntityTypes::SimilarMeme).await;
+    similar_meme(bot, msg, &meme, &s_meme).await
+}
 
-        bot.send_message(
-            msg.chat.id,
-            message.replace("{user_name}", &user_text).replace(
-                "{percent}",
-                &crate::app::utils::Messages::pluralize(s_meme.percent, ("процент", "процента", "процентов")),
-            ),
-        )
-        .reply_parameters(ReplyParameters::new(s_meme.meme.unwrap().msg_id()))
-        .reply_markup(
-            DeleteMarkup::new(meme.uuid)
-                .set_ok_text("🗑 Упс, действительно, было...")
-                .set_none_text("❌ Это точно свежак!")
-                .get_markup(),
-        )
-        .await?;
+/// Animations and stickers are compared by their thumbnails
+async fn thumbnail_similar(
+    app: &Application,
+    msg: &Message,
+    thumbnail: Option<&PhotoSize>,
+) -> (Vec<ImageHashes>, SimilarMeme) {
+    match thumbnail_hashes(app, thumbnail).await {
+        Some(hashes) => {
+            let s_meme = app.get_similar_meme(msg.chat.id.0, &hashes).await;
+
+            (vec![hashes], s_meme)
+        }
+        None => (Vec::new(), SimilarMeme::default()),
     }
+}
 
-    Ok(())
+async fn thumbnail_hashes(app: &Application, thumbnail: Option<&PhotoSize>) -> Option<ImageHashes> {
+    match app.generate_hashes(&thumbnail?.file.id).await {
+        Ok(hashes) => Some(hashes),
+        Err(e) => {
+            warn!("Can't generate thumbnail hashes. Error: {e}");
+            None
+        }
+    }
 }
 
-async fn video_handle(bot: &Bot, msg: &Message) -> anyhow::Result<()> {
-    let user = msg.from.as_ref().unwrap();
-    let video = if let Some(photos) = msg.video() {
-        photos
-    } else {
-        return Ok(());
+pub(super) async fn meme_already_exists(bot: &Bot, msg: &Message, meme: &MemeModel) -> anyhow::Result<()> {
+    let language = Chats::get_language(msg.chat.id.0).await;
+    let context = TemplateContext {
+        chat: Some(&msg.chat),
+        user: msg.from.as_ref(),
+        meme: Some(meme),
+        ..Default::default()
     };
-    let user_text = crate::app::utils::get_user_text(user);
+    let message = templates::random_text(EntityTypes::MemeAlreadyExists, language, &context).await;
 
-    let meme = match Memes::add(msg, &None, &None).await {
-        None => {
-            warn!("Meme is empty after insert!");
-            return Ok(());
-        }
-        Some(m) => m,
-    };
+    bot.send_message(msg.chat.id, message)
+        .reply_parameters(ReplyParameters::new(meme.msg_id()))
+        .await?;
 
-    bot.delete_message(msg.chat.id, msg.id).await?;
+    Ok(())
+}
 
-    let markup = MemeMarkup::new(0, 0, meme.uuid);
-    let caption = if let Some(caption) = msg.caption() {
-        format!("\n\nС подписью: {caption}")
-    } else {
-        String::new()
+pub(super) async fn similar_meme(
+    bot: &Bot,
+    msg: &Message,
+    meme: &MemeModel,
+    s_meme: &SimilarMeme,
+) -> anyhow::Result<()> {
+    let Some(similar) = s_meme.meme.as_ref() else {
+        return Ok(());
     };
 
-    let bot_msg = bot
-        .send_video(msg.chat.id, InputFile::file_id(&video.file.id))
-        .caption(format!("Оцените видео-мем {user_text}{caption}"))
-        .reply_markup(markup.get_markup())
-        .await?;
+    let language = Chats::get_language(msg.chat.id.0).await;
+    let context = TemplateContext {
+        chat: Some(&msg.chat),
+        user: msg.from.as_ref(),
+        meme: Some(similar),
+        percent: Some(s_meme.percent),
+    };
+    let message = templates::random_text(EntityTypes::SimilarMeme, language, &context).await;
 
-    meme.replace_msg_id(bot_msg.id.0 as i64).await;
+    bot.send_message(msg.chat.id, message)
+        .reply_parameters(ReplyParameters::new(similar.msg_id()))
+        .reply_markup(
+            DeleteMarkup::new(meme.uuid)
+                .set_ok_text(&language.text("similar-delete"))
+                .set_none_text(&language.text("similar-keep"))
+                .get_markup(),
+        )
+        .await?;
 
     Ok(())
 }
@@ -6,24 +6,28 @@ use teloxide::{
     prelude::*,
 };
 
+mod albums;
 mod callbacks;
 mod commands;
+mod complaints;
 mod markups;
 mod messages;
 mod types;
 
 pub fn scheme() -> UpdateHandler<anyhow::Error> {
-    let chat_id = BotManager::global().chat_id;
     dptree::entry()
+        .branch(Update::filter_my_chat_member().endpoint(messages::my_chat_member_handle))
         .branch(
             Update::filter_message()
-                .filter(move |m: Message| BotManager::filter_messages(&m.chat, chat_id))
+                .filter(move |m: Message| BotManager::filter_messages(&m.chat))
                 .branch(
                     Update::filter_message()
                         .filter_command::<PublicCommand>()
                         .branch(dptree::case![PublicCommand::Accordion].endpoint(commands::accordion_command))
                         .branch(dptree::case![PublicCommand::F].endpoint(commands::f_command))
                         .branch(dptree::case![PublicCommand::Stats].endpoint(commands::stats_command))
+                        .branch(dptree::case![PublicCommand::Me].endpoint(commands::me_command))
+                        .branch(dptree::case![PublicCommand::Top(args)].endpoint(commands::top_command))
                         .branch(dptree::case![PublicCommand::UnMeme].endpoint(commands::unmeme_command))
                         .branch(dptree::case![PublicCommand::Help].endpoint(commands::help_command)),
                 )
@@ -31,14 +35,20 @@ pub fn scheme() -> UpdateHandler<anyhow::Error> {
         )
         .branch(
             Update::filter_chat_member()
-                .filter(move |cm: ChatMemberUpdated| BotManager::filter_messages(&cm.chat, chat_id))
+                .filter(move |cm: ChatMemberUpdated| BotManager::filter_messages(&cm.chat))
                 .endpoint(messages::chat_member_handle),
         )
         .branch(
             Update::filter_callback_query()
                 .filter(move |c: CallbackQuery| {
-                    c.message.is_some() && BotManager::filter_messages(c.message.unwrap().chat(), chat_id)
+                    c.message.is_some() && BotManager::filter_messages(c.message.unwrap().chat())
                 })
+                .branch(
+                    dptree::filter_map(|c: CallbackQuery| {
+                        serde_json::from_str::<types::TopCallback>(c.data.as_deref()?).ok()
+                    })
+                    .endpoint(callbacks::CallbackHandler::top_handle),
+                )
                 .endpoint(callbacks::CallbackHandler::public_handle),
         )
 }
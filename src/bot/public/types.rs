@@ -1,3 +1,5 @@
+use crate::app::utils::Period;
+use crate::database::entity::users::Leaderboard;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -7,6 +9,8 @@ pub enum CallbackOperations {
     Dislike,
     Delete,
     None,
+    /// Complaint of a member that the meme is a repost
+    Complain,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -14,3 +18,30 @@ pub struct MemeCallback {
     pub uuid: Uuid,
     pub op: CallbackOperations,
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum TopPeriod {
+    Week,
+    Month,
+    Year,
+    All,
+}
+
+impl TopPeriod {
+    pub fn period(&self) -> Period {
+        match *self {
+            TopPeriod::Week => Period::Week,
+            TopPeriod::Month => Period::Month,
+            TopPeriod::Year => Period::Year,
+            TopPeriod::All => Period::all_time(),
+        }
+    }
+}
+
+/// Page of the `/top` leaderboard
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct TopCallback {
+    pub top: Leaderboard,
+    pub period: TopPeriod,
+    pub page: usize,
+}
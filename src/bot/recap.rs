@@ -0,0 +1,136 @@
+use crate::app::calendar::Calendar;
+use crate::app::locale::Language;
+use crate::app::utils::Period;
+use crate::bot::BotManager;
+use crate::database::entity::{
+    prelude::{Memes, RecapRuns, Users},
+    users,
+};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use teloxide::prelude::*;
+use teloxide::utils::html;
+
+/// Width of the biggest bar of months
+const BAR_WIDTH: i64 = 10;
+
+/// Dates of the year by the calendar, the current one is not over yet
+pub fn year_dates(year: i32) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let middle = NaiveDate::from_ymd_opt(year, 7, 1)?.and_hms_opt(0, 0, 0)?.and_utc();
+
+    Some(Period::Year.dates_at(Calendar::global(), middle))
+}
+
+/// Personal recap of the year in all chats, `None` if the user has no memes then
+pub async fn recap_text(
+    user_id: i64,
+    year: i32,
+    dates: (DateTime<Utc>, DateTime<Utc>),
+    language: Language,
+) -> Option<String> {
+    let (from, to) = dates;
+    let recap = Users::get_recap(user_id, from, to, Calendar::global().timezone()).await;
+
+    if recap.memes == 0 {
+        return None;
+    }
+
+    let mut text = language.format(
+        "recap-title",
+        &[("year", &year), ("memes", &language.plural("memes", recap.memes))],
+    );
+
+    if let Some(meme) = Memes::get_max_liked_of_user_everywhere(user_id, from, to).await {
+        let likes = meme.count_all_likes().await.map(|c| c.likes).unwrap_or_default();
+        let likes = language.plural("likes", likes);
+        let chat = BotManager::global().get_chat(meme.chat_id).await.ok();
+
+        match Message::url_of(meme.chat_id(), chat.as_ref().and_then(|c| c.username()), meme.msg_id()) {
+            Some(url) => text.push_str(&format!(
+                "\n{}",
+                language.format("best-meme-link", &[("url", &url), ("likes", &likes)])
+            )),
+            None => text.push_str(&format!("\n{}", language.format("best-meme", &[("likes", &likes)]))),
+        }
+    }
+
+    if let Some(fan) = &recap.fan {
+        text.push_str(&format!(
+            "\n{}",
+            language.format(
+                "recap-fan",
+                &[
+                    ("user", &user_name(fan.user_id, language).await),
+                    ("likes", &language.plural("likes", fan.count)),
+                ],
+            )
+        ));
+    }
+
+    if let Some(favourite) = &recap.favourite {
+        text.push_str(&format!(
+            "\n{}",
+            language.format(
+                "recap-favourite",
+                &[
+                    ("user", &user_name(favourite.user_id, language).await),
+                    ("likes", &language.plural("likes", favourite.count)),
+                ],
+            )
+        ));
+    }
+
+    text.push_str(&format!(
+        "\n{}",
+        language.format("recap-selflikes", &[("count", &recap.selflikes)])
+    ));
+
+    let max = recap.months.iter().copied().max().unwrap_or_default().max(1);
+
+    for (month, count) in language.text("recap-months").split(',').zip(recap.months) {
+        let bar = "▇".repeat((count * BAR_WIDTH / max) as usize);
+
+        text.push_str(&format!("\n<code>{month} {bar}</code> {count}"));
+    }
+
+    Some(text)
+}
+
+/// Sends recaps of the ended year to users who opted in, once per user
+pub async fn send_recaps() {
+    let calendar = Calendar::global();
+    let dates = Period::Year.last_dates(calendar, Utc::now());
+    let year = calendar.date(dates.1).year();
+    let users = Users::get_recap_subscribers().await;
+
+    info!("Send recaps of {year} to {} users", users.len());
+
+    for user in users {
+        let Some(run) = RecapRuns::claim(user.user_id, year).await else {
+            continue;
+        };
+
+        if let Some(text) = recap_text(user.user_id, year, dates, Language::global()).await {
+            let res = BotManager::global()
+                .get()
+                .send_message(ChatId(user.user_id), text)
+                .await;
+
+            if let Err(e) = res {
+                warn!("Can't send recap to user {}: {e}", user.user_id);
+            }
+        }
+
+        run.finish().await;
+    }
+}
+
+async fn user_name(user_id: i64, language: Language) -> String {
+    match Users::get_by_id(user_id).await {
+        Some(users::Model {
+            username: Some(username),
+            ..
+        }) => format!("@{username}"),
+        Some(user) => html::escape(&user.firstname),
+        None => language.text("recap-someone"),
+    }
+}
@@ -1,28 +1,45 @@
-use crate::app::utils::{get_user_text, Messages, Period};
-use crate::bot::BotManager;
-use crate::database::entity::prelude::{Memes, Users};
+use crate::app::calendar::Calendar;
+use crate::app::chart::ReportChart;
+use crate::app::collage::{self, Tile};
+use crate::app::locale::Language;
+use crate::app::utils::{get_user_text, Period};
+use crate::bot::{recap, BotManager};
+use crate::database::entity::{
+    chats,
+    prelude::{Chats, Memes, ScheduledRuns, Users},
+    users::{Leaderboard, RankedUser},
+};
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
 use futures::future::join_all;
 use futures::FutureExt;
-use teloxide::payloads::SendMessageSetters;
+use teloxide::payloads::{SendMessageSetters, SendPhotoSetters};
 use teloxide::prelude::ChatId;
 use teloxide::requests::Requester;
-use teloxide::types::{MessageId, ReplyParameters};
+use teloxide::types::{InputFile, Message as TgMessage, MessageId, ReplyParameters};
+use teloxide::utils::html;
+
+/// How many places of each leaderboard are sent
+const TOP_SIZE: usize = 3;
+/// How many of the most liked memes are put to the collage
+const COLLAGE_SIZE: u64 = 6;
+/// How many memesenders are drawn on the chart
+const CHART_TOP_SIZE: usize = 5;
 
 #[derive(Debug, Clone)]
 pub struct Message {
     text: String,
-    placeholder: String,
-    user_id: i64,
+    /// Placeholders in the text and users who are put there
+    users: Vec<(String, i64)>,
     separate: bool,
     reply_id: Option<i64>,
 }
 
 impl Message {
-    pub fn new(text: &str, placeholder: &str, user_id: i64) -> Self {
+    pub fn new(text: &str, users: Vec<(String, i64)>) -> Self {
         Self {
             text: text.to_string(),
-            placeholder: placeholder.to_string(),
-            user_id,
+            users,
             separate: false,
             reply_id: None,
         }
@@ -31,8 +48,7 @@ impl Message {
     pub fn new_separate(text: &str, placeholder: &str, user_id: i64) -> Self {
         Self {
             text: text.to_string(),
-            placeholder: placeholder.to_string(),
-            user_id,
+            users: vec![(placeholder.to_string(), user_id)],
             separate: true,
             reply_id: None,
         }
@@ -45,119 +61,419 @@ impl Message {
     }
 }
 
+/// Where statistics goes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Output {
+    /// Posted to the chat of the statistics
+    Chat,
+    /// Printed to stdout, images are saved to the temp directory
+    Stdout,
+    /// Sent to the private chat, without replies to messages of the chat
+    Preview(i64),
+}
+
 pub struct Statistics {
     bot: BotManager,
+    output: Output,
 }
 
 impl Statistics {
     pub fn new() -> Self {
+        Self::with_output(Output::Chat)
+    }
+
+    pub fn with_output(output: Output) -> Self {
         let bot = BotManager::global().clone();
 
-        Self { bot }
+        Self { bot, output }
     }
 
+    /// Sends statistics to all active chats by their own calendars, as the scheduler does
     pub async fn send(&self, period: &Period) {
-        match *period {
-            Period::Week => {
-                if Period::is_today_a_friday() {
-                    info!("Send statistics of week");
-                    self.send_by_period(period).await;
-                } else {
-                    debug!("Today is not a friday!");
+        for (calendar, chat_ids) in by_calendar(&Chats::get_active().await) {
+            self.send_to_chats(period, &chat_ids, &calendar).await;
+        }
+    }
+
+    /// Sends statistics if the period is over today by the calendar.
+    /// Not to the chats it is not recorded, and the period so far is shown if it is not over.
+    pub async fn send_to_chats(&self, period: &Period, chat_ids: &[i64], calendar: &Calendar) {
+        let now = Utc::now();
+
+        if !period.is_due(calendar, now) {
+            info!("Statistics of {period:?} is not sent today, its period is not over");
+
+            if self.output != Output::Chat {
+                let dates = period.dates_at(calendar, now);
+
+                println!("Statistics of {period:?} is not due today, the period so far:");
+
+                for chat_id in chat_ids {
+                    self.send_by_period(*chat_id, period, dates).await;
                 }
             }
-            Period::Month => {
-                if Period::is_today_a_last_month_day() {
-                    info!("Send statistics of month");
-                    self.send_by_period(period).await;
-                } else {
-                    debug!("Today is not a last month day!");
+
+            return;
+        }
+
+        info!("Send statistics of {period:?} to {} chats", chat_ids.len());
+
+        let dates = period.last_dates(calendar, now);
+
+        for chat_id in chat_ids {
+            match self.output {
+                Output::Chat => {
+                    self.send_once(*chat_id, period, dates, None).await;
                 }
+                _ => self.send_by_period(*chat_id, period, dates).await,
             }
-            Period::Year => {
-                if Period::is_today_a_last_year_day() {
-                    info!("Send statistics of year");
-                    self.send_by_period(period).await;
-                } else {
-                    debug!("Today is not a last year day!");
+        }
+
+        if *period == Period::Year && self.output == Output::Chat {
+            recap::send_recaps().await;
+        }
+    }
+
+    /// Statistics of the period which is not over yet, as it is sent at its end
+    pub async fn preview(&self, chat_id: i64, period: &Period) {
+        let calendar = match Chats::get_by_id(chat_id).await {
+            Some(chat) => chat.calendar(),
+            None => Calendar::global().clone(),
+        };
+
+        self.send_by_period(chat_id, period, period.dates_at(&calendar, Utc::now()))
+            .await;
+    }
+
+    /// Statistics of the same chat and dates is sent only once, custom periods are always sent.
+    ///
+    /// With `reclaim_after` it is sent again if its sending was started that long ago and never finished.
+    pub async fn send_once(
+        &self,
+        chat_id: i64,
+        period: &Period,
+        dates: (DateTime<Utc>, DateTime<Utc>),
+        reclaim_after: Option<Duration>,
+    ) -> bool {
+        if let Period::Custom { .. } = period {
+            self.send_by_period(chat_id, period, dates).await;
+            return true;
+        }
+
+        let run = match ScheduledRuns::claim(chat_id, period.name(), dates.0, dates.1).await {
+            Some(run) => Some(run),
+            None => match reclaim_after {
+                Some(after) => {
+                    let claimed_before = (Utc::now() - after).naive_utc();
+
+                    ScheduledRuns::reclaim(chat_id, period.name(), dates.0, dates.1, claimed_before).await
                 }
-            }
-            Period::Custom { .. } => {
-                info!("Send statistics of custom period");
-                self.send_by_period(period).await;
-            }
+                None => None,
+            },
+        };
+
+        let Some(run) = run else {
+            info!(
+                "Statistics of {period:?} from {} is already sent to chat {chat_id}",
+                dates.0
+            );
+            return false;
         };
+
+        self.send_by_period(chat_id, period, dates).await;
+        run.finish().await;
+
+        true
     }
 
-    async fn send_by_period(&self, period: &Period) {
+    /// Users who left the chat are named as they were stored
+    async fn user_text(&self, chat_id: i64, user_id: i64, language: Language) -> String {
+        match self.bot.find_chat_user(chat_id, user_id).await {
+            Some(tg_user) => get_user_text(&tg_user),
+            None => Users::get_by_id(user_id)
+                .await
+                .map(|u| html::escape(&u.firstname))
+                .unwrap_or_else(|| language.text("top-unknown")),
+        }
+    }
+
+    async fn send_by_period(&self, chat_id: i64, period: &Period, dates: (DateTime<Utc>, DateTime<Utc>)) {
+        let language = Chats::get_language(chat_id).await;
         let res = join_all(vec![
-            self.get_top_liked_meme(period).boxed(),
-            self.get_top_memesender(period).boxed(),
-            self.get_top_selfliker(period).boxed(),
-            self.get_top_liker(period).boxed(),
-            self.get_top_disliker(period).boxed(),
-            self.get_top_disliked_meme(period).boxed(),
+            self.get_top_liked_meme(chat_id, period, dates, language).boxed(),
+            self.get_top_memesender(chat_id, period, dates, language).boxed(),
+            self.get_top_selfliker(chat_id, period, dates, language).boxed(),
+            self.get_top_liker(chat_id, period, dates, language).boxed(),
+            self.get_top_disliker(chat_id, period, dates, language).boxed(),
+            self.get_top_disliked_meme(chat_id, period, dates, language).boxed(),
         ])
         .await;
 
         let messages = res.into_iter().flatten().collect::<Vec<Message>>();
 
-        let bot = &self.bot;
-        let chat_id = self.bot.chat_id;
+        if let Output::Stdout = self.output {
+            println!("== Chat {chat_id}, {period:?} from {} to {}", dates.0, dates.1);
+        }
 
         let mut buffer: Vec<String> = Vec::new();
 
         for message in messages {
-            let user = self.bot.get_chat_user(message.user_id).await;
-            let text = message.text.replace(&message.placeholder, &get_user_text(&user));
+            let mut text = message.text.clone();
+
+            for (placeholder, user_id) in &message.users {
+                text = text.replace(placeholder, &self.user_text(chat_id, *user_id, language).await);
+            }
 
             if message.separate {
                 if !buffer.is_empty() {
-                    bot.get()
-                        .send_message(
-                            ChatId(chat_id),
-                            format!("Хотели топов? Их есть у меня!\n\n{}", &buffer.join("\n\n")),
-                        )
-                        .await
-                        .expect("Can't send message");
+                    self.post_text(
+                        chat_id,
+                        &language.format("statistics-tops", &[("tops", &buffer.join("\n\n"))]),
+                        None,
+                    )
+                    .await;
                     buffer.clear();
                 }
 
-                let mut s = bot.get().send_message(ChatId(chat_id), &text);
-
-                if let Some(reply_id) = message.reply_id {
-                    s = s.reply_parameters(ReplyParameters::new(MessageId(reply_id as i32)));
-                }
-
-                s.await.expect("Can't send message");
+                self.post_text(chat_id, &text, message.reply_id).await;
             } else {
                 buffer.push(text);
             }
         }
 
         if !buffer.is_empty() {
-            bot.get()
-                .send_message(
-                    ChatId(chat_id),
-                    format!("Хотели топов? Их есть у меня!\n\n{}", &buffer.join("\n\n")),
-                )
-                .await
-                .expect("Can't send message");
+            self.post_text(
+                chat_id,
+                &language.format("statistics-tops", &[("tops", &buffer.join("\n\n"))]),
+                None,
+            )
+            .await;
             buffer.clear();
         }
+
+        self.send_collage(chat_id, period, dates, language).await;
+
+        if let Period::Week | Period::Month = period {
+            self.send_chart(chat_id, period, dates, language).await;
+        }
+    }
+
+    /// One image of the most liked memes, the caption links to the original messages
+    async fn send_collage(
+        &self,
+        chat_id: i64,
+        period: &Period,
+        dates: (DateTime<Utc>, DateTime<Utc>),
+        language: Language,
+    ) {
+        let (from, to) = dates;
+        let memes = Memes::get_most_liked(chat_id, from, to, COLLAGE_SIZE).await;
+
+        if memes.len() < 2 {
+            return;
+        }
+
+        let chat = self.bot.get_chat(chat_id).await.ok();
+        let mut tiles = Vec::with_capacity(memes.len());
+        let mut lines = Vec::with_capacity(memes.len());
+
+        for (i, meme) in memes.iter().enumerate() {
+            let Some(file_id) = meme.preview_file_id() else {
+                continue;
+            };
+            let path = match self.bot.download_file(&file_id).await {
+                Ok(path) => path,
+                Err(e) => {
+                    warn!("Can't download meme {} for collage: {e}", meme.uuid);
+                    continue;
+                }
+            };
+            let likes = meme.count_all_likes().await.map(|c| c.likes).unwrap_or_default();
+            let likes_text = language.plural("likes", likes);
+            let url = TgMessage::url_of(meme.chat_id(), chat.as_ref().and_then(|c| c.username()), meme.msg_id());
+
+            lines.push(match url {
+                Some(url) => format!("{}. <a href=\"{url}\">{likes_text}</a>", i + 1),
+                None => format!("{}. {likes_text}", i + 1),
+            });
+            tiles.push(Tile {
+                path,
+                rank: i + 1,
+                likes,
+            });
+        }
+
+        let image = collage::render(&tiles);
+
+        for tile in &tiles {
+            std::fs::remove_file(&tile.path).unwrap_or_default();
+        }
+
+        let image = match image {
+            Ok(image) => image,
+            Err(e) => {
+                error!("Can't render collage of chat {chat_id}: {e}");
+                return;
+            }
+        };
+
+        let caption = language.format(
+            "statistics-collage",
+            &[
+                ("period", &Statistics::period_of(period, language)),
+                ("memes", &lines.join("\n")),
+            ],
+        );
+
+        self.post_photo(chat_id, image, "memes.png", &caption).await;
     }
 
-    async fn get_top_liked_meme(&self, period: &Period) -> Option<Message> {
-        let (from, to) = period.dates();
+    async fn send_chart(
+        &self,
+        chat_id: i64,
+        period: &Period,
+        dates: (DateTime<Utc>, DateTime<Utc>),
+        language: Language,
+    ) {
+        let chart = self.get_chart(chat_id, dates).await;
+
+        if chart.days.iter().all(|(_, count)| *count == 0) {
+            return;
+        }
+
+        let image = match chart.render() {
+            Ok(image) => image,
+            Err(e) => {
+                error!("Can't render statistics chart of chat {chat_id}: {e}");
+                return;
+            }
+        };
 
-        if let Some(meme) = Memes::get_max_liked(from, to).await {
+        let caption = language.format(
+            "statistics-chart",
+            &[("period", &Statistics::period_of(period, language))],
+        );
+
+        self.post_photo(chat_id, image, "statistics.png", &caption).await;
+    }
+
+    async fn post_text(&self, chat_id: i64, text: &str, reply_id: Option<i64>) {
+        let target = match self.output {
+            Output::Chat => chat_id,
+            Output::Preview(target) => target,
+            Output::Stdout => {
+                println!("{text}\n");
+                return;
+            }
+        };
+
+        let mut s = self.bot.get().send_message(ChatId(target), text);
+
+        if let (Output::Chat, Some(reply_id)) = (self.output, reply_id) {
+            s = s.reply_parameters(ReplyParameters::new(MessageId(reply_id as i32)));
+        }
+
+        if let Err(e) = s.await {
+            error!("Can't send statistics of chat {chat_id} to {target}: {e}");
+        }
+    }
+
+    async fn post_photo(&self, chat_id: i64, image: Vec<u8>, file_name: &str, caption: &str) {
+        let target = match self.output {
+            Output::Chat => chat_id,
+            Output::Preview(target) => target,
+            Output::Stdout => {
+                let path = std::env::temp_dir().join(format!("{chat_id}-{file_name}"));
+
+                match std::fs::write(&path, image) {
+                    Ok(_) => println!("[{}]\n{caption}\n", path.display()),
+                    Err(e) => println!("[Can't save {file_name}: {e}]\n{caption}\n"),
+                }
+
+                return;
+            }
+        };
+
+        let res = self
+            .bot
+            .get()
+            .send_photo(
+                ChatId(target),
+                InputFile::memory(image).file_name(file_name.to_string()),
+            )
+            .caption(caption)
+            .await;
+
+        if let Err(e) = res {
+            error!("Can't send {file_name} of chat {chat_id} to {target}: {e}");
+        }
+    }
+
+    async fn get_chart(&self, chat_id: i64, dates: (DateTime<Utc>, DateTime<Utc>)) -> ReportChart {
+        let (from, to) = dates;
+        let calendar = match Chats::get_by_id(chat_id).await {
+            Some(chat) => chat.calendar(),
+            None => Calendar::global().clone(),
+        };
+        let first = calendar.date(from);
+        let days_count = (calendar.date(to) - first).num_days() + 1;
+        let format = if days_count > 10 { "%d" } else { "%d.%m" };
+        let mut chart = ReportChart {
+            days: first
+                .iter_days()
+                .take(days_count as usize)
+                .map(|day| (day.format(format).to_string(), 0))
+                .collect(),
+            ..Default::default()
+        };
+
+        for score in Memes::get_scores(chat_id, from, to).await {
+            if let Some(posted_at) = score.posted_at {
+                let day = (calendar.date(posted_at.and_utc()) - first).num_days();
+
+                if let Some((_, count)) = chart.days.get_mut(day as usize) {
+                    *count += 1;
+                }
+            }
+
+            ReportChart::bucket(&mut chart.likes, score.likes);
+            ReportChart::bucket(&mut chart.dislikes, score.dislikes);
+        }
+
+        for user in Users::top(chat_id, Leaderboard::Memesenders, from, to, CHART_TOP_SIZE).await {
+            let name = match self.bot.find_chat_user(chat_id, user.user_id).await {
+                Some(tg_user) => chart_name(tg_user.username.as_deref(), &tg_user.first_name, user.rank),
+                None => match Users::get_by_id(user.user_id).await {
+                    Some(stored) => chart_name(stored.username.as_deref(), &stored.firstname, user.rank),
+                    None => format!("#{}", user.rank),
+                },
+            };
+
+            chart.senders.push((name, user.count));
+        }
+
+        chart
+    }
+
+    async fn get_top_liked_meme(
+        &self,
+        chat_id: i64,
+        period: &Period,
+        dates: (DateTime<Utc>, DateTime<Utc>),
+        language: Language,
+    ) -> Option<Message> {
+        let (from, to) = dates;
+
+        if let Some(meme) = Memes::get_max_liked(chat_id, from, to).await {
             let placeholder = String::from("{USERNAME}");
             let like_counts = meme.count_all_likes().await?;
-            let text = format!(
-                "{} твой мем набрал {}!\nБольше всех {}!\nПоздравляю! 🎉",
-                &placeholder,
-                Messages::pluralize(like_counts.likes, ("лайк", "лайка", "лайков")),
-                Statistics::get_translations(period).1
+            let text = language.format(
+                "statistics-liked-meme",
+                &[
+                    ("user", &placeholder),
+                    ("likes", &language.plural("likes", like_counts.likes)),
+                    ("period", &Statistics::period_in(period, language)),
+                ],
             );
 
             return Some(Message::new_separate(&text, &placeholder, meme.user_id).set_reply_id(meme.msg_id));
@@ -166,20 +482,28 @@ impl Statistics {
         None
     }
 
-    async fn get_top_disliked_meme(&self, period: &Period) -> Option<Message> {
+    async fn get_top_disliked_meme(
+        &self,
+        chat_id: i64,
+        period: &Period,
+        dates: (DateTime<Utc>, DateTime<Utc>),
+        language: Language,
+    ) -> Option<Message> {
         if *period != Period::Week {
             return None;
         }
 
-        let (from, to) = period.dates();
+        let (from, to) = dates;
 
-        if let Some(meme) = Memes::get_max_disliked(from, to).await {
+        if let Some(meme) = Memes::get_max_disliked(chat_id, from, to).await {
             let placeholder = String::from("{USERNAME}");
             let like_counts = meme.count_all_likes().await?;
-            let text = format!(
-                "Вы только посмотрите, {} на твой мем наставили {}!\nТы точно уверен что делаешь все правильно? Может тебе больше не стоит заниматься юмором? 🤔",
-                &placeholder,
-                Messages::pluralize(like_counts.dislikes, ("дизлайк", "дизлайка", "дизлайков"))
+            let text = language.format(
+                "statistics-disliked-meme",
+                &[
+                    ("user", &placeholder),
+                    ("dislikes", &language.plural("dislikes", like_counts.dislikes)),
+                ],
             );
 
             return Some(Message::new_separate(&text, &placeholder, meme.user_id).set_reply_id(meme.msg_id));
@@ -188,105 +512,193 @@ impl Statistics {
         None
     }
 
-    async fn get_top_memesender(&self, period: &Period) -> Option<Message> {
-        let (from, to) = period.dates();
-        let res = Users::top_memesender(from, to).await;
+    async fn get_top_memesender(
+        &self,
+        chat_id: i64,
+        period: &Period,
+        dates: (DateTime<Utc>, DateTime<Utc>),
+        language: Language,
+    ) -> Option<Message> {
+        let (from, to) = dates;
+        let top = Users::top(chat_id, Leaderboard::Memesenders, from, to, TOP_SIZE).await;
+
+        Statistics::top_message(language, "statistics-memesenders", period, &top, "memes")
+    }
 
-        if let Some(top_user) = res {
-            let placeholder = String::from("{MEMESENDER}");
-            let period_text = Statistics::get_translations(period);
+    async fn get_top_selfliker(
+        &self,
+        chat_id: i64,
+        period: &Period,
+        dates: (DateTime<Utc>, DateTime<Utc>),
+        language: Language,
+    ) -> Option<Message> {
+        let (from, to) = dates;
+        let mut top = Users::top(chat_id, Leaderboard::Selflikers, from, to, TOP_SIZE).await;
 
-            let text = format!(
-                "🤡 Мемомёт {}:\n{} отправил {} {}!",
-                period_text.0,
-                &placeholder,
-                Messages::pluralize(top_user.count, ("мем", "мема", "мемов")),
-                period_text.1
-            );
+        top.retain(|u| u.count > 4);
 
-            return Some(Message::new(&text, &placeholder, top_user.user_id));
-        }
-
-        None
+        Statistics::top_message(language, "statistics-selflikers", period, &top, "times")
     }
 
-    async fn get_top_selfliker(&self, period: &Period) -> Option<Message> {
-        let (from, to) = period.dates();
-        let res = Users::top_selfliker(from, to).await;
-
-        if let Some(top_user) = res {
-            let placeholder = String::from("{SELFLIKER}");
-            let period_text = Statistics::get_translations(period);
+    async fn get_top_liker(
+        &self,
+        chat_id: i64,
+        period: &Period,
+        dates: (DateTime<Utc>, DateTime<Utc>),
+        language: Language,
+    ) -> Option<Message> {
+        let (from, to) = dates;
+        let top = Users::top(chat_id, Leaderboard::Likers, from, to, TOP_SIZE).await;
+
+        Statistics::top_message(language, "statistics-likers", period, &top, "likes")
+    }
 
-            if top_user.count > 4 {
-                let text = format!(
-                    "😈 Хитрец {}:\n{} лайкнул свои же мемы {} {}!",
-                    period_text.0,
-                    &placeholder,
-                    Messages::pluralize(top_user.count, ("раз", "раза", "раз")),
-                    period_text.1
-                );
+    async fn get_top_disliker(
+        &self,
+        chat_id: i64,
+        period: &Period,
+        dates: (DateTime<Utc>, DateTime<Utc>),
+        language: Language,
+    ) -> Option<Message> {
+        let (from, to) = dates;
+        let top = Users::top(chat_id, Leaderboard::Dislikers, from, to, TOP_SIZE).await;
+
+        Statistics::top_message(language, "statistics-dislikers", period, &top, "dislikes")
+    }
 
-                return Some(Message::new(&text, &placeholder, top_user.user_id));
-            }
+    /// Title of the key and places of the top with their counts of the plural key
+    fn top_message(
+        language: Language,
+        title: &str,
+        period: &Period,
+        top: &[RankedUser],
+        counts: &str,
+    ) -> Option<Message> {
+        if top.is_empty() {
+            return None;
         }
 
-        None
+        let users = top
+            .iter()
+            .enumerate()
+            .map(|(i, user)| (format!("{{USER_{i}}}"), user.user_id))
+            .collect::<Vec<(String, i64)>>();
+        let lines = top
+            .iter()
+            .zip(&users)
+            .map(|(user, (placeholder, _))| {
+                format!("{}. {placeholder} — {}", user.rank, language.plural(counts, user.count))
+            })
+            .collect::<Vec<String>>();
+
+        let title = language.format(title, &[("period", &Statistics::period_of(period, language))]);
+
+        Some(Message::new(&format!("{title}\n{}", lines.join("\n")), users))
     }
 
-    async fn get_top_liker(&self, period: &Period) -> Option<Message> {
-        let (from, to) = period.dates();
-        let res = Users::top_liker(from, to).await;
-
-        if let Some(top_user) = res {
-            let placeholder = String::from("{LIKER}");
-            let period_text = Statistics::get_translations(period);
-
-            let text = format!(
-                "❤️ Добродеятель {}:\n{} поставил больше всех лайков {}!\nЦелых {}",
-                period_text.0,
-                &placeholder,
-                period_text.1,
-                Messages::pluralize(top_user.count, ("лайк", "лайка", "лайков")),
-            );
+    /// Like "of the week"
+    fn period_of(period: &Period, language: Language) -> String {
+        language.text(&format!("period-of-{}", period.name()))
+    }
 
-            return Some(Message::new(&text, &placeholder, top_user.user_id));
+    /// Like "this week"
+    fn period_in(period: &Period, language: Language) -> String {
+        match *period {
+            Period::Custom { from, to } => language.format(
+                "period-in-custom",
+                &[("from", &from.format("%Y-%m-%d")), ("to", &to.format("%Y-%m-%d"))],
+            ),
+            _ => language.text(&format!("period-in-{}", period.name())),
         }
+    }
+}
 
-        None
+/// Name of the user for the chart, its font has only ASCII
+fn chart_name(username: Option<&str>, first_name: &str, rank: usize) -> String {
+    let name = match username {
+        Some(username) => format!("@{username}"),
+        None => first_name.to_string(),
+    };
+
+    if name.is_empty() || !name.is_ascii() {
+        return format!("#{rank}");
     }
 
-    async fn get_top_disliker(&self, period: &Period) -> Option<Message> {
-        let (from, to) = period.dates();
-        let res = Users::top_disliker(from, to).await;
+    name.chars().take(16).collect()
+}
 
-        if let Some(top_user) = res {
-            let placeholder = String::from("{DISLIKER}");
-            let period_text = Statistics::get_translations(period);
+/// Chats with the same timezone share a calendar, and so the windows of statistics
+fn by_calendar(chats: &[chats::Model]) -> Vec<(Calendar, Vec<i64>)> {
+    let mut groups: Vec<(Option<Tz>, Calendar, Vec<i64>)> = Vec::new();
 
-            let text = format!(
-                "😡 Засранец {}:\n{} поставил больше всех дизлайков {}!\nЦелых {}",
-                period_text.0,
-                &placeholder,
-                period_text.1,
-                Messages::pluralize(top_user.count, ("дизлайк", "дизлайка", "дизлайков")),
-            );
+    for chat in chats {
+        let timezone = chat.timezone();
 
-            return Some(Message::new(&text, &placeholder, top_user.user_id));
+        match groups.iter_mut().find(|(tz, _, _)| *tz == timezone) {
+            Some((_, _, chat_ids)) => chat_ids.push(chat.chat_id),
+            None => groups.push((timezone, chat.calendar(), vec![chat.chat_id])),
         }
+    }
 
-        None
+    groups
+        .into_iter()
+        .map(|(_, calendar, chat_ids)| (calendar, chat_ids))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::calendar::{CalendarConfig, INSTANCE};
+    use chrono::NaiveTime;
+
+    fn chat(chat_id: i64, timezone: Option<&str>) -> chats::Model {
+        chats::Model {
+            chat_id,
+            chatname: None,
+            description: None,
+            created_at: None,
+            title: None,
+            deleted_at: None,
+            similar_threshold: None,
+            duplicate_threshold: None,
+            timezone: timezone.map(str::to_string),
+            schedule: None,
+            language: None,
+        }
     }
 
-    fn get_translations(period: &Period) -> (String, String) {
-        match *period {
-            Period::Week => ("недели".to_owned(), "на этой неделе".to_owned()),
-            Period::Month => ("месяца".to_owned(), "в этом месяце".to_owned()),
-            Period::Year => ("года".to_owned(), "в этом году".to_owned()),
-            Period::Custom { from, to } => (
-                "периода".to_owned(),
-                format!("в периоде с {} по {}", from.format("%Y-%m-%d"), to.format("%Y-%m-%d")),
-            ),
+    #[test]
+    fn cli_claims_the_window_of_the_scheduler() {
+        INSTANCE.get_or_init(|| {
+            Calendar::new(&CalendarConfig {
+                timezone: Tz::UTC,
+                cutoff: NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+                weekend: "Sat,Sun".parse().unwrap(),
+                holidays_file: None,
+            })
+            .unwrap()
+        });
+
+        let chats = [chat(1, None), chat(2, Some("Asia/Vladivostok")), chat(3, None)];
+        // Friday 17:00 in Vladivostok, the week is over there but not in UTC
+        let now: DateTime<Utc> = "2026-01-30T07:00:00Z".parse().unwrap();
+        let groups = by_calendar(&chats);
+
+        assert_eq!(groups.len(), 2);
+
+        for chat in &chats {
+            let (calendar, _) = groups.iter().find(|(_, ids)| ids.contains(&chat.chat_id)).unwrap();
+
+            assert_eq!(
+                Period::Week.last_dates(calendar, now),
+                Period::Week.last_dates(&chat.calendar(), now)
+            );
         }
+
+        assert_ne!(
+            Period::Week.last_dates(&chats[1].calendar(), now),
+            Period::Week.last_dates(Calendar::global(), now)
+        );
     }
 }
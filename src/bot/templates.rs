@@ -0,0 +1,96 @@
+use crate::app::locale::Language;
+use crate::app::template::{Placeholder, Template, TemplateValues};
+use crate::app::utils::get_user_text;
+use crate::bot::BotManager;
+use crate::database::entity::{
+    memes,
+    messages::EntityTypes,
+    prelude::{Memes, Messages, Users},
+};
+use chrono::{Duration, Utc};
+use teloxide::types::{Chat, User};
+use teloxide::utils::html;
+
+/// What a stored message is sent about, placeholders of missing things are left empty
+#[derive(Default)]
+pub struct TemplateContext<'a> {
+    pub chat: Option<&'a Chat>,
+    pub user: Option<&'a User>,
+    /// The meme which was already sent
+    pub meme: Option<&'a memes::Model>,
+    pub percent: Option<i64>,
+}
+
+/// Random stored text of the type filled from the context. Texts which are not valid templates are sent as they are.
+pub async fn random_text(entity_type: EntityTypes, language: Language, context: &TemplateContext<'_>) -> String {
+    let text = Messages::get_random_text(entity_type.clone(), language, context.chat.map(|c| c.id.0)).await;
+
+    match Template::parse(&text) {
+        Ok(template) => render(&template, language, context).await,
+        Err(e) => {
+            warn!("Wrong template of {entity_type:?}: {e}");
+            html::escape(&text)
+        }
+    }
+}
+
+/// HTML of the template, values are fetched only for its placeholders
+pub async fn render(template: &Template, language: Language, context: &TemplateContext<'_>) -> String {
+    let mut values = TemplateValues::default();
+
+    for placeholder in template.placeholders() {
+        match placeholder {
+            Placeholder::UserName => {
+                if let Some(user) = context.user {
+                    values.set_html(placeholder, get_user_text(user));
+                }
+            }
+            Placeholder::AuthorName => {
+                if let (Some(chat), Some(meme)) = (context.chat, context.meme) {
+                    match BotManager::global().find_chat_user(chat.id.0, meme.user_id).await {
+                        Some(author) => values.set_html(placeholder, get_user_text(&author)),
+                        None => {
+                            if let Some(author) = Users::get_by_id(meme.user_id).await {
+                                values.set_text(placeholder, &author.firstname);
+                            }
+                        }
+                    }
+                }
+            }
+            Placeholder::MemeAge => {
+                if let Some(posted_at) = context.meme.and_then(|m| m.posted_at) {
+                    values.set_text(placeholder, &age(language, Utc::now() - posted_at.and_utc()));
+                }
+            }
+            Placeholder::Percent => {
+                if let Some(percent) = context.percent {
+                    values.set_text(placeholder, &language.plural("percents", percent));
+                }
+            }
+            Placeholder::ChatTitle => {
+                if let Some(title) = context.chat.and_then(|c| c.title()) {
+                    values.set_text(placeholder, title);
+                }
+            }
+            Placeholder::UserMemes => {
+                if let (Some(chat), Some(user)) = (context.chat, context.user) {
+                    let count = Memes::get_user_count(chat.id.0, user.id.0 as i64).await;
+
+                    values.set_text(placeholder, &language.plural("memes", count as i64));
+                }
+            }
+        }
+    }
+
+    template.render(&values)
+}
+
+/// Like `3 days`, in the biggest whole units
+fn age(language: Language, duration: Duration) -> String {
+    match duration.num_days() {
+        0 => language.plural("hours", duration.num_hours().max(1)),
+        days @ 1..=29 => language.plural("days", days),
+        days @ 30..=364 => language.plural("months", days / 30),
+        days => language.plural("years", days / 365),
+    }
+}
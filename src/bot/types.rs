@@ -1,9 +1,44 @@
-use crate::database::entity::{chats, memes, users};
+use crate::app::imghash::ImageHashes;
+use crate::database::entity::{chats, memes, memes::MediaKind, users};
 use crate::database::Database;
-use sea_orm::ActiveModelTrait;
-use sea_orm::Set;
+use sea_orm::{ActiveModelTrait, EntityTrait, Set};
+use serde::{Deserialize, Serialize};
 use teloxide::prelude::{ChatId, Message, UserId};
-use teloxide::types::{Chat, MessageId, User};
+use teloxide::types::{Animation, Chat, MessageId, PhotoSize, Sticker, User, Video};
+
+/// Item of an album meme. Albums stored before videos were kept are lists of photo sizes only.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AlbumItem {
+    Photo(Vec<PhotoSize>),
+    Video(Box<Video>),
+}
+
+impl AlbumItem {
+    pub fn of(message: &Message) -> Option<Self> {
+        match (message.photo(), message.video()) {
+            (Some(photos), _) => Some(AlbumItem::Photo(photos.to_vec())),
+            (_, Some(video)) => Some(AlbumItem::Video(Box::new(video.clone()))),
+            _ => None,
+        }
+    }
+
+    /// Biggest photo or the video file
+    pub fn file_id(&self) -> Option<String> {
+        match self {
+            AlbumItem::Photo(photos) => photos.last().map(|p| p.file.id.clone()),
+            AlbumItem::Video(video) => Some(video.file.id.clone()),
+        }
+    }
+
+    /// Biggest photo or the thumbnail of the video
+    pub fn preview(&self) -> Option<PhotoSize> {
+        match self {
+            AlbumItem::Photo(photos) => photos.last().cloned(),
+            AlbumItem::Video(video) => video.thumbnail.clone(),
+        }
+    }
+}
 
 impl From<User> for users::ActiveModel {
     fn from(value: User) -> Self {
@@ -30,13 +65,33 @@ impl From<Chat> for chats::ActiveModel {
 }
 
 impl memes::Entity {
-    pub async fn add(message: &Message, l_hash: &Option<String>, s_hash: &Option<String>) -> Option<memes::Model> {
-        let json = if message.photo().is_some() {
-            Option::from(serde_json::json!(message.photo()))
-        } else if message.video().is_some() {
-            Option::from(serde_json::json!(message.video()))
+    pub async fn add(message: &Message, hashes: Option<&ImageHashes>) -> Option<memes::Model> {
+        let (kind, json, file_unique_id) = if let Some(photos) = message.photo() {
+            (
+                MediaKind::Photo,
+                Option::from(serde_json::json!(photos)),
+                photos.last().map(|p| p.file.unique_id.clone()),
+            )
+        } else if let Some(video) = message.video() {
+            (
+                MediaKind::Video,
+                Option::from(serde_json::json!(video)),
+                Some(video.file.unique_id.clone()),
+            )
+        } else if let Some(animation) = message.animation() {
+            (
+                MediaKind::Animation,
+                Option::from(serde_json::json!(animation)),
+                Some(animation.file.unique_id.clone()),
+            )
+        } else if let Some(sticker) = message.sticker() {
+            (
+                MediaKind::Sticker,
+                Option::from(serde_json::json!(sticker)),
+                Some(sticker.file.unique_id.clone()),
+            )
         } else {
-            None
+            (MediaKind::Photo, None, None)
         };
 
         let res = memes::ActiveModel {
@@ -44,8 +99,13 @@ impl memes::Entity {
             user_id: Set(message.from.as_ref().unwrap().id.0 as i64),
             chat_id: Set(message.chat.id.0),
             photos: Set(json),
-            long_hash: Set(l_hash.clone()),
-            short_hash: Set(s_hash.clone()),
+            long_hash: Set(hashes.map(|h| h.long_hash.to_bytes())),
+            short_hash: Set(hashes.map(|h| h.short_hash.to_i64())),
+            perceptual_hash: Set(hashes.map(|h| h.perceptual_hash.to_i64())),
+            difference_hash: Set(hashes.map(|h| h.difference_hash.to_i64())),
+            file_unique_id: Set(file_unique_id),
+            media_kind: Set(kind),
+            caption: Set(message.caption().map(|c| c.to_string())),
             ..Default::default()
         }
         .insert(Database::global().connection())
@@ -59,6 +119,31 @@ impl memes::Entity {
             }
         }
     }
+
+    pub async fn add_album(messages: &[Message]) -> Option<memes::Model> {
+        let first = messages.first()?;
+        let items: Vec<_> = messages.iter().filter_map(AlbumItem::of).collect();
+
+        let res = memes::ActiveModel {
+            msg_id: Set(Some(first.id.0 as i64)),
+            user_id: Set(first.from.as_ref().unwrap().id.0 as i64),
+            chat_id: Set(first.chat.id.0),
+            photos: Set(Some(serde_json::json!(items))),
+            media_kind: Set(MediaKind::Album),
+            caption: Set(messages.iter().find_map(|m| m.caption()).map(|c| c.to_string())),
+            ..Default::default()
+        }
+        .insert(Database::global().connection())
+        .await;
+
+        match res {
+            Ok(m) => Some(m),
+            Err(e) => {
+                error!("Can't add album meme to database: {e}");
+                None
+            }
+        }
+    }
 }
 
 impl memes::Model {
@@ -73,21 +158,89 @@ impl memes::Model {
     pub fn msg_id(&self) -> MessageId {
         MessageId(self.msg_id.unwrap() as i32)
     }
+
+    /// Image of the meme: its biggest photo, the first photo of its album or a thumbnail
+    pub fn preview_file_id(&self) -> Option<String> {
+        let photos = self.photos.clone()?;
+        let photo = match self.media_kind {
+            MediaKind::Photo => serde_json::from_value::<Vec<PhotoSize>>(photos).ok()?.pop(),
+            MediaKind::Album => serde_json::from_value::<Vec<AlbumItem>>(photos)
+                .ok()?
+                .first()?
+                .preview(),
+            MediaKind::Video => serde_json::from_value::<Video>(photos).ok()?.thumbnail,
+            MediaKind::Animation => serde_json::from_value::<Animation>(photos).ok()?.thumbnail,
+            MediaKind::Sticker => serde_json::from_value::<Sticker>(photos).ok()?.thumbnail,
+        };
+
+        photo.map(|p| p.file.id)
+    }
+
+    pub fn album_msg_ids(&self) -> Vec<MessageId> {
+        self.album_msg_ids
+            .as_ref()
+            .and_then(|ids| serde_json::from_value::<Vec<i32>>(ids.clone()).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(MessageId)
+            .collect()
+    }
 }
 
 impl chats::Entity {
-    #[allow(dead_code)]
-    pub async fn add(tg_chat: &Chat) -> Option<chats::Model> {
-        let res = chats::ActiveModel::from(tg_chat.clone())
-            .insert(Database::global().connection())
+    pub async fn add(tg_chat: &Chat) -> bool {
+        let res = chats::Entity::insert(chats::ActiveModel::from(tg_chat.clone()))
+            .on_conflict(chats::Entity::on_conflict())
+            .exec(Database::global().connection())
             .await;
 
         match res {
-            Ok(c) => Some(c),
+            Ok(_) => true,
             Err(e) => {
-                error!("Can't add user to database: {e}");
-                None
+                error!("Can't add chat to database: {e}");
+                false
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn photo(id: &str) -> serde_json::Value {
+        json!({"file_id": id, "file_unique_id": id, "file_size": 1, "width": 90, "height": 90})
+    }
+
+    #[test]
+    fn albums_of_photos_only_are_read() {
+        let album = json!([[photo("a1"), photo("a2")], [photo("b1")]]);
+        let items: Vec<AlbumItem> = serde_json::from_value(album).unwrap();
+
+        assert_eq!(
+            items.iter().filter_map(AlbumItem::file_id).collect::<Vec<_>>(),
+            ["a2", "b1"]
+        );
+    }
+
+    #[test]
+    fn albums_with_videos_are_read() {
+        let video = json!({
+            "file_id": "v",
+            "file_unique_id": "v",
+            "file_size": 1,
+            "width": 90,
+            "height": 90,
+            "duration": 3,
+            "thumbnail": photo("t"),
+            "file_name": null,
+            "mime_type": null,
+        });
+        let items: Vec<AlbumItem> = serde_json::from_value(json!([[photo("a")], video])).unwrap();
+
+        assert!(matches!(items[1], AlbumItem::Video(_)));
+        assert_eq!(items[1].file_id().as_deref(), Some("v"));
+        assert_eq!(items[1].preview().map(|p| p.file.id), Some(String::from("t")));
+    }
+}
@@ -1,6 +1,6 @@
 use crate::database::Database;
 use futures::{executor::block_on, future::join_all};
-use sea_orm::{entity::prelude::*, QuerySelect, Set};
+use sea_orm::{entity::prelude::*, sea_query::OnConflict, QuerySelect, Set};
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "chat_admins")]
@@ -18,7 +18,17 @@ pub enum Relation {}
 impl ActiveModelBehavior for ActiveModel {}
 
 impl Entity {
-    pub fn add_admins(chat_id: i64, admins_ids: &[u64]) {
+    pub async fn set_admins(chat_id: i64, admins_ids: &[u64]) {
+        let res = Entity::delete_many()
+            .filter(Column::ChatId.eq(chat_id))
+            .filter(Column::UserId.is_not_in(admins_ids.iter().map(|id| *id as i64)))
+            .exec(Database::global().connection())
+            .await;
+
+        if let Err(e) = res {
+            error!("Can't remove old chat admins from database: {e}");
+        }
+
         let futures: Vec<_> = admins_ids
             .iter()
             .map(|admin_id| {
@@ -27,11 +37,17 @@ impl Entity {
                     user_id: Set(*admin_id as i64),
                     ..Default::default()
                 })
+                .on_conflict(
+                    OnConflict::columns([Column::ChatId, Column::UserId])
+                        .do_nothing()
+                        .to_owned(),
+                )
+                .do_nothing()
                 .exec(Database::global().connection())
             })
             .collect();
 
-        block_on(join_all(futures));
+        join_all(futures).await;
     }
 
     pub async fn get_admin_chats(user_id: u64) -> Vec<i64> {
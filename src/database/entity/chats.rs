@@ -1,4 +1,9 @@
-use sea_orm::entity::prelude::*;
+use crate::app::{calendar::Calendar, locale::Language, Thresholds};
+use crate::database::Database;
+use crate::scheduler::Schedule;
+use chrono::Utc;
+use chrono_tz::Tz;
+use sea_orm::{entity::prelude::*, sea_query::OnConflict, Set};
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "chats")]
@@ -10,9 +15,152 @@ pub struct Model {
     pub created_at: Option<DateTime>,
     pub title: Option<String>,
     pub deleted_at: Option<DateTime>,
+    pub similar_threshold: Option<i16>,
+    pub duplicate_threshold: Option<i16>,
+    pub timezone: Option<String>,
+    pub schedule: Option<String>,
+    pub language: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {}
 
 impl ActiveModelBehavior for ActiveModel {}
+
+impl Entity {
+    pub async fn get_by_id(chat_id: i64) -> Option<Model> {
+        let res = Self::find_by_id(chat_id).one(Database::global().connection()).await;
+
+        res.unwrap_or_else(|e| {
+            error!("Can't get chat from database: {e}");
+            None
+        })
+    }
+
+    pub async fn get_active() -> Vec<Model> {
+        let res = Self::find()
+            .filter(Column::DeletedAt.is_null())
+            .all(Database::global().connection())
+            .await;
+
+        res.unwrap_or_else(|e| {
+            error!("Can't get chats from database: {e}");
+            Vec::new()
+        })
+    }
+
+    pub async fn remove(chat_id: i64) -> bool {
+        Entity::update(ActiveModel {
+            chat_id: Set(chat_id),
+            deleted_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        })
+        .exec(Database::global().connection())
+        .await
+        .is_ok()
+    }
+
+    /// `None` resets a threshold to the default one
+    pub async fn set_thresholds(chat_id: i64, similar: Option<i16>, duplicate: Option<i16>) -> bool {
+        Entity::update(ActiveModel {
+            chat_id: Set(chat_id),
+            similar_threshold: Set(similar),
+            duplicate_threshold: Set(duplicate),
+            ..Default::default()
+        })
+        .exec(Database::global().connection())
+        .await
+        .is_ok()
+    }
+
+    /// `None` resets the chat to the default timezone and schedule
+    pub async fn set_schedule(chat_id: i64, timezone: Option<String>, schedule: Option<String>) -> bool {
+        Entity::update(ActiveModel {
+            chat_id: Set(chat_id),
+            timezone: Set(timezone),
+            schedule: Set(schedule),
+            ..Default::default()
+        })
+        .exec(Database::global().connection())
+        .await
+        .is_ok()
+    }
+
+    /// `None` resets the chat to the default language
+    pub async fn set_language(chat_id: i64, language: Option<Language>) -> bool {
+        Entity::update(ActiveModel {
+            chat_id: Set(chat_id),
+            language: Set(language.map(|l| l.code().to_string())),
+            ..Default::default()
+        })
+        .exec(Database::global().connection())
+        .await
+        .is_ok()
+    }
+
+    /// Language of the chat, the default one for unknown chats
+    pub async fn get_language(chat_id: i64) -> Language {
+        match Self::get_by_id(chat_id).await {
+            Some(chat) => chat.language(),
+            None => Language::global(),
+        }
+    }
+
+    pub(crate) fn on_conflict() -> OnConflict {
+        OnConflict::column(Column::ChatId)
+            .update_columns([Column::Chatname, Column::Title, Column::Description])
+            .value(Column::DeletedAt, Expr::val(None::<DateTime>))
+            .to_owned()
+    }
+}
+
+impl Model {
+    pub fn has_own_schedule(&self) -> bool {
+        self.timezone.is_some() || self.schedule.is_some()
+    }
+
+    pub fn timezone(&self) -> Option<Tz> {
+        self.timezone.as_deref().and_then(|tz| {
+            tz.parse()
+                .map_err(|e| warn!("Wrong timezone of chat {}: {e}", self.chat_id))
+                .ok()
+        })
+    }
+
+    /// The default calendar in the timezone of the chat
+    pub fn calendar(&self) -> Calendar {
+        match self.timezone() {
+            Some(timezone) => Calendar::global().with_timezone(timezone),
+            None => Calendar::global().clone(),
+        }
+    }
+
+    /// Overrides of the default schedule
+    pub fn schedule(&self) -> Option<Schedule> {
+        self.schedule.as_deref().and_then(|schedule| {
+            schedule
+                .parse()
+                .map_err(|e| warn!("Wrong schedule of chat {}: {e}", self.chat_id))
+                .ok()
+        })
+    }
+
+    pub fn language(&self) -> Language {
+        self.language
+            .as_deref()
+            .and_then(|language| {
+                language
+                    .parse()
+                    .map_err(|e| warn!("Wrong language of chat {}: {e}", self.chat_id))
+                    .ok()
+            })
+            .unwrap_or_else(Language::global)
+    }
+
+    pub fn thresholds(&self, default: Thresholds) -> Thresholds {
+        Thresholds {
+            similar: self.similar_threshold.map(f64::from).unwrap_or(default.similar),
+            duplicate: self.duplicate_threshold.map(f64::from).unwrap_or(default.duplicate),
+        }
+    }
+}
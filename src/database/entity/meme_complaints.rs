@@ -0,0 +1,90 @@
+use crate::database::Database;
+use chrono::Utc;
+use sea_orm::{entity::prelude::*, sea_query::OnConflict, PaginatorTrait, Set};
+
+/// Complaints of members that a meme is a repost
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "meme_complaints")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub uuid: Uuid,
+    pub meme_uuid: Uuid,
+    pub user_id: i64,
+    pub created_at: Option<DateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::memes::Entity",
+        from = "Column::MemeUuid",
+        to = "super::memes::Column::Uuid",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Memes,
+}
+
+impl Related<super::memes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Memes.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Entity {
+    /// A repeated complaint of the user starts the time of the complaint again
+    pub async fn add(meme_uuid: Uuid, user_id: i64) -> bool {
+        Entity::insert(ActiveModel {
+            meme_uuid: Set(meme_uuid),
+            user_id: Set(user_id),
+            created_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([Column::MemeUuid, Column::UserId])
+                .update_column(Column::CreatedAt)
+                .to_owned(),
+        )
+        .exec(Database::global().connection())
+        .await
+        .is_ok()
+    }
+
+    pub async fn exists(meme_uuid: Uuid, user_id: i64) -> bool {
+        let res = Entity::find()
+            .filter(Column::MemeUuid.eq(meme_uuid))
+            .filter(Column::UserId.eq(user_id))
+            .count(Database::global().connection())
+            .await;
+
+        res.unwrap_or_else(|e| {
+            error!("Can't check complaint in database: {e}");
+            0
+        }) > 0
+    }
+
+    pub async fn remove(meme_uuid: Uuid, user_id: i64) -> bool {
+        Entity::delete_many()
+            .filter(Column::MemeUuid.eq(meme_uuid))
+            .filter(Column::UserId.eq(user_id))
+            .exec(Database::global().connection())
+            .await
+            .is_ok()
+    }
+
+    /// Members who complained about the meme since the time
+    pub async fn count_since(meme_uuid: Uuid, since: DateTime) -> u64 {
+        let res = Entity::find()
+            .filter(Column::MemeUuid.eq(meme_uuid))
+            .filter(Column::CreatedAt.gte(since))
+            .count(Database::global().connection())
+            .await;
+
+        res.unwrap_or_else(|e| {
+            error!("Can't count complaints in database: {e}");
+            0
+        })
+    }
+}
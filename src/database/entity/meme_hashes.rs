@@ -0,0 +1,117 @@
+use crate::app::imghash::{Hash, HashAlgorithm, ImageHashes};
+use crate::database::Database;
+use sea_orm::{entity::prelude::*, JoinType, QueryOrder, QuerySelect, Set};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "meme_hashes")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub uuid: Uuid,
+    pub meme_uuid: Uuid,
+    pub position: i16,
+    pub long_hash: Option<Vec<u8>>,
+    pub short_hash: Option<i64>,
+    pub perceptual_hash: Option<i64>,
+    pub difference_hash: Option<i64>,
+    pub created_at: Option<DateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::memes::Entity",
+        from = "Column::MemeUuid",
+        to = "super::memes::Column::Uuid",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Memes,
+}
+
+impl Related<super::memes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Memes.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Entity {
+    pub async fn add(meme_uuid: Uuid, hashes: &[ImageHashes]) -> bool {
+        if hashes.is_empty() {
+            return true;
+        }
+
+        let models = hashes.iter().enumerate().map(|(position, h)| ActiveModel {
+            meme_uuid: Set(meme_uuid),
+            position: Set(position as i16),
+            long_hash: Set(Some(h.long_hash.to_bytes())),
+            short_hash: Set(Some(h.short_hash.to_i64())),
+            perceptual_hash: Set(Some(h.perceptual_hash.to_i64())),
+            difference_hash: Set(Some(h.difference_hash.to_i64())),
+            ..Default::default()
+        });
+
+        let res = Entity::insert_many(models).exec(Database::global().connection()).await;
+
+        match res {
+            Ok(_) => true,
+            Err(e) => {
+                error!("Can't add meme hashes to database: {e}");
+                false
+            }
+        }
+    }
+
+    pub async fn replace(meme_uuid: Uuid, hashes: &[ImageHashes]) -> bool {
+        let res = Entity::delete_many()
+            .filter(Column::MemeUuid.eq(meme_uuid))
+            .exec(Database::global().connection())
+            .await;
+
+        if let Err(e) = res {
+            error!("Can't remove meme hashes from database: {e}");
+            return false;
+        }
+
+        Self::add(meme_uuid, hashes).await
+    }
+
+    /// Hashes of the frames of the meme in their order
+    pub async fn get_by_meme(meme_uuid: Uuid) -> Vec<Model> {
+        let res = Self::find()
+            .filter(Column::MemeUuid.eq(meme_uuid))
+            .order_by_asc(Column::Position)
+            .all(Database::global().connection())
+            .await;
+
+        res.unwrap_or_else(|e| {
+            error!("Can't get meme hashes from database: {e}");
+            Vec::new()
+        })
+    }
+
+    pub async fn get_by_chat(chat_id: i64) -> Vec<Model> {
+        let res = Self::find()
+            .join(JoinType::InnerJoin, Relation::Memes.def())
+            .filter(super::memes::Column::ChatId.eq(chat_id))
+            .all(Database::global().connection())
+            .await;
+
+        res.unwrap_or_else(|e| {
+            error!("Can't get meme hashes from database: {e}");
+            Vec::new()
+        })
+    }
+}
+
+impl Model {
+    pub fn hash(&self, algorithm: HashAlgorithm, long_bits: usize) -> Option<Hash> {
+        algorithm.pick_column(
+            self.long_hash.as_deref(),
+            self.perceptual_hash,
+            self.difference_hash,
+            long_bits,
+        )
+    }
+}
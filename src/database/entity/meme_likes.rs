@@ -1,6 +1,7 @@
-use crate::database::Database;
+use crate::database::{entity::RowStream, Database};
 use sea_orm::{entity::prelude::*, sea_query::OnConflict};
-use sea_orm::{FromQueryResult, QuerySelect, QueryTrait, Set};
+use sea_orm::{FromQueryResult, JoinType, QueryOrder, QuerySelect, QueryTrait, Set};
+use serde::Serialize;
 
 #[derive(DeriveIden)]
 pub enum MemeLikes {
@@ -58,6 +59,15 @@ pub struct MemeLikesCountAll {
     pub dislikes: i64,
 }
 
+#[derive(FromQueryResult, Serialize, Debug, Clone)]
+pub struct VoteExport {
+    pub meme_uuid: Option<Uuid>,
+    pub user_id: i64,
+    /// 1 is a like, -1 is a dislike
+    pub num: i16,
+    pub created_at: Option<DateTime>,
+}
+
 pub enum MemeLikeOperation {
     Like,
     Dislike,
@@ -119,9 +129,38 @@ impl Entity {
             .is_ok()
     }
 
+    /// Votes given in the period to memes of the chat
+    pub async fn export(chat_id: i64, from: DateTimeUtc, to: DateTimeUtc) -> Result<RowStream<VoteExport>, DbErr> {
+        Entity::find()
+            .select_only()
+            .columns([Column::MemeUuid, Column::UserId, Column::Num, Column::CreatedAt])
+            .join(JoinType::InnerJoin, Relation::Memes.def())
+            .filter(super::memes::Column::ChatId.eq(chat_id))
+            .filter(Column::CreatedAt.gte(from))
+            .filter(Column::CreatedAt.lte(to))
+            .order_by_asc(Column::CreatedAt)
+            .into_model::<VoteExport>()
+            .stream(Database::global().connection())
+            .await
+    }
+
     pub async fn count_all(meme_uuid: Option<Uuid>) -> Option<MemeLikesCountAll> {
+        Self::count_all_by(meme_uuid, None).await
+    }
+
+    pub async fn count_all_by_chat(chat_id: i64) -> Option<MemeLikesCountAll> {
+        Self::count_all_by(None, Some(chat_id)).await
+    }
+
+    async fn count_all_by(meme_uuid: Option<Uuid>, chat_id: Option<i64>) -> Option<MemeLikesCountAll> {
         let res = Entity::find()
             .apply_if(meme_uuid, |query, v| query.filter(Column::MemeUuid.eq(v)))
+            .apply_if(chat_id, |query, v| {
+                query
+                    .join(JoinType::InnerJoin, Relation::Memes.def())
+                    .filter(super::memes::Column::ChatId.eq(v))
+                    .filter(super::memes::Column::RepostedAt.is_null())
+            })
             .select_only()
             .column_as(
                 Expr::cust(r#"COUNT("meme_likes"."num") FILTER (WHERE "meme_likes"."num" = 1)"#),
@@ -1,9 +1,13 @@
 use super::meme_likes::{MemeLikeOperation, MemeLikesCountAll};
-use crate::database::{entity::prelude::MemeLikes, Database};
+use crate::app::imghash::{Hash, HashAlgorithm, ImageHashes};
+use crate::database::{
+    entity::{prelude::MemeLikes, RowStream},
+    Database,
+};
 use sea_orm::entity::prelude::*;
 use sea_orm::{
     sea_query::{Alias, Order},
-    JoinType, QueryOrder, QuerySelect, Set,
+    Condition, FromQueryResult, JoinType, QueryOrder, QuerySelect, QueryTrait, Set,
 };
 
 #[derive(DeriveIden)]
@@ -23,12 +27,38 @@ pub struct Model {
     pub photos: Option<Json>,
     pub posted_at: Option<DateTime>,
     pub updated_at: Option<DateTime>,
-    pub long_hash: Option<String>,
-    pub short_hash: Option<String>,
+    pub long_hash: Option<Vec<u8>>,
+    pub short_hash: Option<i64>,
+    pub perceptual_hash: Option<i64>,
+    pub difference_hash: Option<i64>,
+    pub file_unique_id: Option<String>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub album_msg_ids: Option<Json>,
+    pub media_kind: MediaKind,
+    pub caption: Option<String>,
+    /// Members voted that it is a repost, it is left out of statistics
+    pub reposted_at: Option<DateTime>,
+}
+
+#[derive(EnumIter, DeriveActiveEnum, Clone, Copy, Debug, PartialEq, Eq)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = r#""MediaKind""#)]
+pub enum MediaKind {
+    #[sea_orm(string_value = "photo")]
+    Photo,
+    #[sea_orm(string_value = "video")]
+    Video,
+    #[sea_orm(string_value = "album")]
+    Album,
+    #[sea_orm(string_value = "animation")]
+    Animation,
+    #[sea_orm(string_value = "sticker")]
+    Sticker,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::meme_hashes::Entity")]
+    MemeHashes,
     #[sea_orm(has_many = "super::meme_likes::Entity")]
     MemeLikes,
     #[sea_orm(
@@ -41,6 +71,12 @@ pub enum Relation {
     Users,
 }
 
+impl Related<super::meme_hashes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MemeHashes.def()
+    }
+}
+
 impl Related<super::meme_likes::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::MemeLikes.def()
@@ -66,9 +102,17 @@ impl Entity {
     }
 
     pub async fn get_by_msg_id(chat_id: i64, msg_id: u64) -> Option<Model> {
+        // Album memes are also found by any of their re-posted photos
         let res = Self::find()
             .filter(Column::ChatId.eq(chat_id))
-            .filter(Column::MsgId.eq(msg_id))
+            .filter(
+                Condition::any()
+                    .add(Column::MsgId.eq(msg_id))
+                    .add(Expr::cust_with_values(
+                        r#""album_msg_ids" @> $1"#,
+                        [serde_json::json!([msg_id])],
+                    )),
+            )
             .one(Database::global().connection())
             .await;
 
@@ -78,21 +122,66 @@ impl Entity {
         })
     }
 
-    pub async fn get_by_short_hash(hash: &str) -> Vec<Model> {
+    /// All memes of the chat in the order they were posted
+    pub async fn get_by_chat(chat_id: i64) -> Vec<Model> {
+        let res = Self::find()
+            .filter(Column::ChatId.eq(chat_id))
+            .order_by(Column::PostedAt, Order::Asc)
+            .all(Database::global().connection())
+            .await;
+
+        res.unwrap_or_else(|e| {
+            error!("Can't get memes from database: {e}");
+            Vec::new()
+        })
+    }
+
+    /// Memes after `after` in uuid order, for walking the whole table
+    pub async fn get_page(chat_id: Option<i64>, after: Option<Uuid>, limit: u64) -> Vec<Model> {
+        let res = Self::find()
+            .apply_if(chat_id, |query, chat_id| query.filter(Column::ChatId.eq(chat_id)))
+            .apply_if(after, |query, after| query.filter(Column::Uuid.gt(after)))
+            .order_by(Column::Uuid, Order::Asc)
+            .limit(limit)
+            .all(Database::global().connection())
+            .await;
+
+        res.unwrap_or_else(|e| {
+            error!("Can't get memes from database: {e}");
+            Vec::new()
+        })
+    }
+
+    pub async fn get_hashed(chat_id: i64) -> Vec<Model> {
         let res = Self::find()
-            .filter(Column::ShortHash.eq(hash))
+            .filter(Column::ChatId.eq(chat_id))
+            .filter(Column::LongHash.is_not_null())
             .all(Database::global().connection())
             .await;
 
         res.unwrap_or_else(|e| {
-            error!("Can't get memes by short hash from database: {e}");
+            error!("Can't get hashed memes from database: {e}");
             Vec::new()
         })
     }
 
+    pub async fn get_by_file_unique_id(chat_id: i64, file_unique_id: &str) -> Option<Model> {
+        let res = Self::find()
+            .filter(Column::ChatId.eq(chat_id))
+            .filter(Column::FileUniqueId.eq(file_unique_id))
+            .one(Database::global().connection())
+            .await;
+
+        res.unwrap_or_else(|e| {
+            error!("Can't get meme from database: {e}");
+            None
+        })
+    }
+
     pub async fn get_count(chat_id: i64) -> u64 {
         let res = Self::find()
             .filter(Column::ChatId.eq(chat_id))
+            .filter(Column::RepostedAt.is_null())
             .count(Database::global().connection())
             .await;
 
@@ -102,10 +191,58 @@ impl Entity {
         })
     }
 
-    pub async fn get_max_liked(from: DateTimeUtc, to: DateTimeUtc) -> Option<Model> {
+    pub async fn get_user_count(chat_id: i64, user_id: i64) -> u64 {
+        let res = Self::find()
+            .filter(Column::ChatId.eq(chat_id))
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::RepostedAt.is_null())
+            .count(Database::global().connection())
+            .await;
+
+        res.unwrap_or_else(|e| {
+            error!("Can't get meme count of user: {e}");
+            0
+        })
+    }
+
+    pub async fn get_max_liked(chat_id: i64, from: DateTimeUtc, to: DateTimeUtc) -> Option<Model> {
+        Self::get_most_liked_by(Some(chat_id), None, from, to, 1).await.pop()
+    }
+
+    pub async fn get_max_liked_of_user(
+        chat_id: i64,
+        user_id: i64,
+        from: DateTimeUtc,
+        to: DateTimeUtc,
+    ) -> Option<Model> {
+        Self::get_most_liked_by(Some(chat_id), Some(user_id), from, to, 1)
+            .await
+            .pop()
+    }
+
+    /// The most liked meme of the user in all chats
+    pub async fn get_max_liked_of_user_everywhere(user_id: i64, from: DateTimeUtc, to: DateTimeUtc) -> Option<Model> {
+        Self::get_most_liked_by(None, Some(user_id), from, to, 1).await.pop()
+    }
+
+    /// Memes by likes of the period, the most liked first
+    pub async fn get_most_liked(chat_id: i64, from: DateTimeUtc, to: DateTimeUtc, limit: u64) -> Vec<Model> {
+        Self::get_most_liked_by(Some(chat_id), None, from, to, limit).await
+    }
+
+    async fn get_most_liked_by(
+        chat_id: Option<i64>,
+        user_id: Option<i64>,
+        from: DateTimeUtc,
+        to: DateTimeUtc,
+        limit: u64,
+    ) -> Vec<Model> {
         let res = Self::find()
             .column_as(super::meme_likes::Column::Num.sum(), "likes")
             .join(JoinType::InnerJoin, Relation::MemeLikes.def())
+            .filter(Column::RepostedAt.is_null())
+            .apply_if(chat_id, |query, chat_id| query.filter(Column::ChatId.eq(chat_id)))
+            .apply_if(user_id, |query, user_id| query.filter(Column::UserId.eq(user_id)))
             .filter(super::meme_likes::Column::CreatedAt.gt(from))
             .filter(super::meme_likes::Column::CreatedAt.lte(to))
             .filter(super::meme_likes::Column::Num.eq(MemeLikeOperation::Like.id()))
@@ -114,19 +251,114 @@ impl Entity {
             .having(Expr::expr(super::meme_likes::Column::Num.sum()).gt(0))
             .order_by(Expr::col(Alias::new("likes")), Order::Desc)
             .order_by(Column::PostedAt, Order::Desc)
-            .one(Database::global().connection())
+            .limit(limit)
+            .all(Database::global().connection())
             .await;
 
         res.unwrap_or_else(|e| {
             error!("Can't get meme from database: {e}");
-            None
+            Vec::new()
+        })
+    }
+
+    /// Memes posted in the period with counts of their likes and dislikes
+    pub async fn get_scores(chat_id: i64, from: DateTimeUtc, to: DateTimeUtc) -> Vec<MemeScore> {
+        let res = Self::find()
+            .select_only()
+            .column(Column::PostedAt)
+            .column_as(
+                Expr::cust(r#"COUNT("meme_likes"."num") FILTER (WHERE "meme_likes"."num" = 1)"#),
+                "likes",
+            )
+            .column_as(
+                Expr::cust(r#"COUNT("meme_likes"."num") FILTER (WHERE "meme_likes"."num" = -1)"#),
+                "dislikes",
+            )
+            .join(JoinType::LeftJoin, Relation::MemeLikes.def())
+            .filter(Column::ChatId.eq(chat_id))
+            .filter(Column::RepostedAt.is_null())
+            .filter(Column::PostedAt.gte(from))
+            .filter(Column::PostedAt.lte(to))
+            .group_by(Column::Uuid)
+            .into_model::<MemeScore>()
+            .all(Database::global().connection())
+            .await;
+
+        res.unwrap_or_else(|e| {
+            error!("Can't get meme scores from database: {e}");
+            Vec::new()
         })
     }
 
-    pub async fn get_max_disliked(from: DateTimeUtc, to: DateTimeUtc) -> Option<Model> {
+    /// Meme from the chat history which the bot has never seen, it has no media to post or to rehash
+    pub async fn add_imported(
+        chat_id: i64,
+        user_id: i64,
+        msg_id: i64,
+        posted_at: DateTime,
+        caption: Option<String>,
+        hashes: &ImageHashes,
+    ) -> Option<Model> {
+        let res = ActiveModel {
+            msg_id: Set(Some(msg_id)),
+            user_id: Set(user_id),
+            chat_id: Set(chat_id),
+            posted_at: Set(Some(posted_at)),
+            long_hash: Set(Some(hashes.long_hash.to_bytes())),
+            short_hash: Set(Some(hashes.short_hash.to_i64())),
+            perceptual_hash: Set(Some(hashes.perceptual_hash.to_i64())),
+            difference_hash: Set(Some(hashes.difference_hash.to_i64())),
+            media_kind: Set(MediaKind::Photo),
+            caption: Set(caption),
+            ..Default::default()
+        }
+        .insert(Database::global().connection())
+        .await;
+
+        res.map_err(|e| error!("Can't add imported meme to database: {e}")).ok()
+    }
+
+    /// Memes of the chat posted in the period with their authors and votes
+    pub async fn export(chat_id: i64, from: DateTimeUtc, to: DateTimeUtc) -> Result<RowStream<MemeExport>, DbErr> {
+        Self::find()
+            .select_only()
+            .columns([
+                Column::Uuid,
+                Column::MsgId,
+                Column::UserId,
+                Column::PostedAt,
+                Column::MediaKind,
+                Column::Caption,
+                Column::Photos,
+            ])
+            .column(super::users::Column::Username)
+            .column_as(
+                Expr::cust(r#"COUNT("meme_likes"."num") FILTER (WHERE "meme_likes"."num" = 1)"#),
+                "likes",
+            )
+            .column_as(
+                Expr::cust(r#"COUNT("meme_likes"."num") FILTER (WHERE "meme_likes"."num" = -1)"#),
+                "dislikes",
+            )
+            .join(JoinType::LeftJoin, Relation::Users.def())
+            .join(JoinType::LeftJoin, Relation::MemeLikes.def())
+            .filter(Column::ChatId.eq(chat_id))
+            .filter(Column::PostedAt.gte(from))
+            .filter(Column::PostedAt.lte(to))
+            .group_by(Column::Uuid)
+            .group_by(super::users::Column::Username)
+            .order_by_asc(Column::PostedAt)
+            .into_model::<MemeExport>()
+            .stream(Database::global().connection())
+            .await
+    }
+
+    pub async fn get_max_disliked(chat_id: i64, from: DateTimeUtc, to: DateTimeUtc) -> Option<Model> {
         let res = Self::find()
             .column_as(super::meme_likes::Column::Num.sum(), "dislikes")
             .join(JoinType::InnerJoin, Relation::MemeLikes.def())
+            .filter(Column::ChatId.eq(chat_id))
+            .filter(Column::RepostedAt.is_null())
             .filter(super::meme_likes::Column::CreatedAt.gte(from))
             .filter(super::meme_likes::Column::CreatedAt.lte(to))
             .filter(super::meme_likes::Column::Num.eq(MemeLikeOperation::Dislike.id()))
@@ -145,7 +377,45 @@ impl Entity {
     }
 }
 
+#[derive(FromQueryResult, Debug, Clone)]
+pub struct MemeExport {
+    pub uuid: Uuid,
+    pub msg_id: Option<i64>,
+    pub user_id: i64,
+    pub username: Option<String>,
+    pub posted_at: Option<DateTime>,
+    pub media_kind: MediaKind,
+    pub caption: Option<String>,
+    pub photos: Option<Json>,
+    pub likes: i64,
+    pub dislikes: i64,
+}
+
+#[derive(FromQueryResult, Debug, Clone)]
+pub struct MemeScore {
+    pub posted_at: Option<DateTime>,
+    pub likes: i64,
+    pub dislikes: i64,
+}
+
 impl Model {
+    pub fn hash(&self, algorithm: HashAlgorithm, long_bits: usize) -> Option<Hash> {
+        algorithm.pick_column(
+            self.long_hash.as_deref(),
+            self.perceptual_hash,
+            self.difference_hash,
+            long_bits,
+        )
+    }
+
+    pub async fn mark_reposted(&self) -> bool {
+        let mut model: ActiveModel = self.clone().into();
+
+        model.reposted_at = Set(Some(chrono::Utc::now().naive_utc()));
+
+        model.update(Database::global().connection()).await.is_ok()
+    }
+
     pub async fn replace_msg_id(&self, msg_id: i64) -> bool {
         let mut model: ActiveModel = self.clone().into();
 
@@ -154,6 +424,25 @@ impl Model {
         model.update(Database::global().connection()).await.is_ok()
     }
 
+    pub async fn replace_hashes(&self, hashes: Option<&ImageHashes>) -> bool {
+        let mut model: ActiveModel = self.clone().into();
+
+        model.long_hash = Set(hashes.map(|h| h.long_hash.to_bytes()));
+        model.short_hash = Set(hashes.map(|h| h.short_hash.to_i64()));
+        model.perceptual_hash = Set(hashes.map(|h| h.perceptual_hash.to_i64()));
+        model.difference_hash = Set(hashes.map(|h| h.difference_hash.to_i64()));
+
+        model.update(Database::global().connection()).await.is_ok()
+    }
+
+    pub async fn replace_album_msg_ids(&self, msg_ids: &[i64]) -> bool {
+        let mut model: ActiveModel = self.clone().into();
+
+        model.album_msg_ids = Set(Some(serde_json::json!(msg_ids)));
+
+        model.update(Database::global().connection()).await.is_ok()
+    }
+
     pub async fn like(&self, from_user_id: i64) -> bool {
         MemeLikes::add(self.uuid, from_user_id, MemeLikeOperation::Like).await
     }
@@ -1,6 +1,9 @@
+use crate::app::locale::Language;
 use crate::database::Database;
-use rand::prelude::SliceRandom;
-use sea_orm::{entity::prelude::*, ActiveValue::Set};
+use crate::redis::RedisManager;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use sea_orm::{entity::prelude::*, ActiveValue::Set, Order, QueryOrder};
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "messages")]
@@ -12,6 +15,12 @@ pub struct Model {
     #[sea_orm(column_type = "Text", nullable)]
     pub message: String,
     pub created_at: Option<DateTime>,
+    /// Messages without a language are sent to all chats
+    pub language: Option<String>,
+    /// Disabled messages are kept, but never sent
+    pub enabled: bool,
+    /// Messages are picked in proportion to their weights
+    pub weight: i32,
 }
 
 #[derive(EnumIter, DeriveActiveEnum, Clone, Debug, PartialEq, Eq)]
@@ -27,7 +36,7 @@ pub enum MessageTypes {
     Document,
 }
 
-#[derive(EnumIter, DeriveActiveEnum, Clone, Debug, PartialEq, Eq)]
+#[derive(EnumIter, DeriveActiveEnum, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[sea_orm(rs_type = "String", db_type = "Enum", enum_name = r#""MessageEntityType""#)]
 pub enum EntityTypes {
     #[sea_orm(string_value = "meme_already_exists")]
@@ -48,11 +57,17 @@ pub enum Relation {}
 impl ActiveModelBehavior for ActiveModel {}
 
 impl Entity {
-    pub async fn add(message_type: MessageTypes, entity_type: EntityTypes, text: &str) -> bool {
+    pub async fn add(
+        message_type: MessageTypes,
+        entity_type: EntityTypes,
+        text: &str,
+        language: Option<Language>,
+    ) -> bool {
         Entity::insert(ActiveModel {
             r#type: Set(message_type),
             entity_type: Set(entity_type),
             message: Set(text.to_string()),
+            language: Set(language.map(|l| l.code().to_string())),
             ..Default::default()
         })
         .exec(Database::global().connection())
@@ -60,35 +75,258 @@ impl Entity {
         .is_ok()
     }
 
-    pub async fn get_random_text(entity_type: EntityTypes) -> String {
-        if let Some(model) = Self::get_random(MessageTypes::Text, entity_type).await {
+    pub async fn get_by_id(uuid: Uuid) -> Option<Model> {
+        let res = Self::find_by_id(uuid).one(Database::global().connection()).await;
+
+        res.unwrap_or_else(|e| {
+            error!("Can't get message from database: {e}");
+            None
+        })
+    }
+
+    /// All messages of the type, disabled ones too, in the order they were added
+    pub async fn get_by_entity_type(entity_type: EntityTypes) -> Vec<Model> {
+        let res = Self::find()
+            .filter(Column::EntityType.eq(entity_type))
+            .order_by(Column::CreatedAt, Order::Asc)
+            .order_by(Column::Uuid, Order::Asc)
+            .all(Database::global().connection())
+            .await;
+
+        res.unwrap_or_else(|e| {
+            error!("Can't get messages from database: {e}");
+            Vec::new()
+        })
+    }
+
+    pub async fn get_random_text(entity_type: EntityTypes, language: Language, chat_id: Option<i64>) -> String {
+        if let Some(model) = Self::get_random(MessageTypes::Text, entity_type, language, chat_id).await {
             model.message
         } else {
             String::new()
         }
     }
 
-    pub async fn get_random_photo(entity_type: EntityTypes) -> String {
-        if let Some(model) = Self::get_random(MessageTypes::Photo, entity_type).await {
+    pub async fn get_random_photo(entity_type: EntityTypes, language: Language, chat_id: Option<i64>) -> String {
+        if let Some(model) = Self::get_random(MessageTypes::Photo, entity_type, language, chat_id).await {
             model.message
         } else {
             String::new()
         }
     }
 
-    pub async fn get_random(message_type: MessageTypes, entity_type: EntityTypes) -> Option<Model> {
+    pub async fn get_random(
+        message_type: MessageTypes,
+        entity_type: EntityTypes,
+        language: Language,
+        chat_id: Option<i64>,
+    ) -> Option<Model> {
+        let key = format!("{}_{}", entity_type.to_value(), message_type.to_value());
+        let messages = Self::get_sendable(message_type, entity_type).await;
+        let seed = rand::thread_rng().gen();
+
+        let Some(chat_id) = chat_id else {
+            return pick(&messages, language, &[], None, seed).cloned();
+        };
+
+        let redis = RedisManager::global();
+        let used = redis.get_used_messages(chat_id, &key);
+        let last = redis.get_last_message(chat_id, &key);
+        let model = pick(&messages, language, &used, last, seed)?.clone();
+
+        // Only sent messages were left, so they are sent again from now
+        if used.contains(&model.uuid) {
+            redis.clear_used_messages(chat_id, &key);
+        }
+
+        redis.add_used_message(chat_id, &key, model.uuid);
+        redis.set_last_message(chat_id, &key, model.uuid);
+
+        Some(model)
+    }
+
+    /// Enabled messages of the type which can be picked
+    async fn get_sendable(message_type: MessageTypes, entity_type: EntityTypes) -> Vec<Model> {
         let res = Self::find()
             .filter(Column::Type.eq(message_type))
             .filter(Column::EntityType.eq(entity_type))
+            .filter(Column::Enabled.eq(true))
+            .filter(Column::Weight.gt(0))
             .all(Database::global().connection())
             .await;
 
-        match res {
-            Ok(m) => m.choose(&mut rand::thread_rng()).cloned(),
-            Err(e) => {
-                error!("Can't get texts from database: {e}");
-                None
-            }
+        res.unwrap_or_else(|e| {
+            error!("Can't get texts from database: {e}");
+            Vec::new()
+        })
+    }
+}
+
+/// Random message, more likely the heavier it is. The same seed and arguments pick the same message.
+///
+/// Messages of other languages are picked only if there are none in this one.
+/// Used messages are picked only if all of them are used, and then not the last one if there are others.
+fn pick<'a>(
+    messages: &'a [Model],
+    language: Language,
+    used: &[Uuid],
+    last: Option<Uuid>,
+    seed: u64,
+) -> Option<&'a Model> {
+    let sendable = messages.iter().filter(|m| m.weight > 0).collect::<Vec<&Model>>();
+    let of_language = sendable
+        .iter()
+        .copied()
+        .filter(|m| m.language.as_deref().is_none_or(|l| l == language.code()))
+        .collect::<Vec<&Model>>();
+    let suitable = if of_language.is_empty() { sendable } else { of_language };
+
+    let fresh = suitable
+        .iter()
+        .copied()
+        .filter(|m| !used.contains(&m.uuid))
+        .collect::<Vec<&Model>>();
+    let mut candidates = if fresh.is_empty() {
+        let not_last = suitable
+            .iter()
+            .copied()
+            .filter(|m| Some(m.uuid) != last)
+            .collect::<Vec<&Model>>();
+
+        if not_last.is_empty() {
+            suitable
+        } else {
+            not_last
         }
+    } else {
+        fresh
+    };
+
+    // Database returns rows in any order
+    candidates.sort_by_key(|m| m.uuid);
+
+    let total = candidates.iter().map(|m| m.weight as i64).sum::<i64>();
+
+    if total == 0 {
+        return None;
+    }
+
+    let mut point = StdRng::seed_from_u64(seed).gen_range(0..total);
+
+    candidates.into_iter().find(|m| {
+        if point < m.weight as i64 {
+            return true;
+        }
+
+        point -= m.weight as i64;
+        false
+    })
+}
+
+impl Model {
+    pub async fn set_enabled(&self, enabled: bool) -> bool {
+        let mut model: ActiveModel = self.clone().into();
+
+        model.enabled = Set(enabled);
+
+        model.update(Database::global().connection()).await.is_ok()
+    }
+
+    pub async fn set_weight(&self, weight: i32) -> bool {
+        let mut model: ActiveModel = self.clone().into();
+
+        model.weight = Set(weight);
+
+        model.update(Database::global().connection()).await.is_ok()
+    }
+
+    pub async fn replace_message(&self, text: &str) -> bool {
+        let mut model: ActiveModel = self.clone().into();
+
+        model.message = Set(text.to_string());
+
+        model.update(Database::global().connection()).await.is_ok()
+    }
+
+    pub async fn remove(&self) -> bool {
+        self.clone().delete(Database::global().connection()).await.is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(n: u128, weight: i32, language: Option<&str>) -> Model {
+        Model {
+            uuid: Uuid::from_u128(n),
+            r#type: MessageTypes::Text,
+            entity_type: EntityTypes::SimilarMeme,
+            message: format!("Message {n}"),
+            created_at: None,
+            language: language.map(str::to_string),
+            enabled: true,
+            weight,
+        }
+    }
+
+    fn picked(messages: &[Model], used: &[u128], last: Option<u128>, seed: u64) -> Option<u128> {
+        let used = used.iter().copied().map(Uuid::from_u128).collect::<Vec<Uuid>>();
+
+        pick(messages, Language::En, &used, last.map(Uuid::from_u128), seed).map(|m| m.uuid.as_u128())
+    }
+
+    #[test]
+    fn seed_pins_the_pick() {
+        let messages = [message(1, 1, None), message(2, 2, None), message(3, 3, None)];
+        let mut reversed = messages.clone();
+        reversed.reverse();
+
+        for seed in 0..100 {
+            assert_eq!(picked(&messages, &[], None, seed), picked(&messages, &[], None, seed));
+            assert_eq!(picked(&messages, &[], None, seed), picked(&reversed, &[], None, seed));
+        }
+    }
+
+    #[test]
+    fn heavier_messages_are_picked_more_often() {
+        let messages = [message(1, 1, None), message(2, 2, None), message(3, 3, None)];
+        let mut counts = [0; 3];
+
+        for seed in 0..6000 {
+            counts[picked(&messages, &[], None, seed).unwrap() as usize - 1] += 1;
+        }
+
+        assert!((800..1200).contains(&counts[0]), "{counts:?}");
+        assert!((1800..2200).contains(&counts[1]), "{counts:?}");
+        assert!((2800..3200).contains(&counts[2]), "{counts:?}");
+    }
+
+    #[test]
+    fn messages_of_the_language_go_first() {
+        let messages = [
+            message(1, 1, Some("ru")),
+            message(2, 1, Some("en")),
+            message(3, 1, None),
+        ];
+
+        for seed in 0..100 {
+            assert_ne!(picked(&messages, &[], None, seed), Some(1));
+        }
+
+        assert_eq!(picked(&messages[..1], &[], None, 42), Some(1));
+    }
+
+    #[test]
+    fn used_messages_are_picked_when_all_are_used() {
+        let messages = [message(1, 1, None), message(2, 1, None), message(3, 1, None)];
+
+        for seed in 0..100 {
+            assert_eq!(picked(&messages, &[1, 2], None, seed), Some(3));
+            assert_ne!(picked(&messages, &[1, 2, 3], Some(3), seed), Some(3));
+        }
+
+        assert_eq!(picked(&messages[..1], &[1], Some(1), 42), Some(1));
+        assert_eq!(picked(&[message(1, 0, None)], &[], None, 42), None);
     }
 }
@@ -2,7 +2,18 @@ pub mod prelude;
 
 pub mod chat_admins;
 pub mod chats;
+pub mod meme_complaints;
+pub mod meme_hashes;
 pub mod meme_likes;
 pub mod memes;
 pub mod messages;
+pub mod recap_runs;
+pub mod scheduled_runs;
 pub mod users;
+
+use futures::Stream;
+use sea_orm::DbErr;
+use std::pin::Pin;
+
+/// Rows read from database one by one
+pub type RowStream<T> = Pin<Box<dyn Stream<Item = Result<T, DbErr>> + Send>>;
@@ -1,6 +1,10 @@
 pub use super::chat_admins::Entity as ChatAdmins;
-// pub use super::chats::Entity as Chats;
+pub use super::chats::Entity as Chats;
+pub use super::meme_complaints::Entity as MemeComplaints;
+pub use super::meme_hashes::Entity as MemeHashes;
 pub use super::meme_likes::Entity as MemeLikes;
 pub use super::memes::Entity as Memes;
 pub use super::messages::Entity as Messages;
+pub use super::recap_runs::Entity as RecapRuns;
+pub use super::scheduled_runs::Entity as ScheduledRuns;
 pub use super::users::Entity as Users;
@@ -0,0 +1,58 @@
+use crate::database::Database;
+use chrono::Utc;
+use sea_orm::{entity::prelude::*, sea_query::OnConflict, Set};
+
+/// Ledger of sent recaps, one row per user and year
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "recap_runs")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub uuid: Uuid,
+    pub user_id: i64,
+    pub year: i32,
+    pub finished_at: Option<DateTime>,
+    pub created_at: Option<DateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Entity {
+    /// `None` if the recap is already claimed, by this or another process
+    pub async fn claim(user_id: i64, year: i32) -> Option<Model> {
+        let res = Entity::insert(ActiveModel {
+            user_id: Set(user_id),
+            year: Set(year),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([Column::UserId, Column::Year])
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_with_returning(Database::global().connection())
+        .await;
+
+        match res {
+            Ok(run) => Some(run),
+            // Nothing is returned on conflict
+            Err(DbErr::RecordNotFound(_)) => None,
+            Err(e) => {
+                error!("Can't claim recap run: {e}");
+                None
+            }
+        }
+    }
+}
+
+impl Model {
+    pub async fn finish(&self) -> bool {
+        let mut model: ActiveModel = self.clone().into();
+
+        model.finished_at = Set(Some(Utc::now().naive_utc()));
+
+        model.update(Database::global().connection()).await.is_ok()
+    }
+}
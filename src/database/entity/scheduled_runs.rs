@@ -0,0 +1,105 @@
+use crate::database::Database;
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::{entity::prelude::*, sea_query::OnConflict, Set};
+
+/// Ledger of sent statistics, one row per chat, period and its dates
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "scheduled_runs")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub uuid: Uuid,
+    pub chat_id: i64,
+    pub period: String,
+    pub window_from: DateTime,
+    pub window_to: DateTime,
+    pub finished_at: Option<DateTime>,
+    pub created_at: Option<DateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Entity {
+    /// `None` if the run is already claimed, by this or another process
+    pub async fn claim(chat_id: i64, period: &str, from: DateTimeUtc, to: DateTimeUtc) -> Option<Model> {
+        let res = Entity::insert(ActiveModel {
+            chat_id: Set(chat_id),
+            period: Set(period.to_string()),
+            window_from: Set(from.naive_utc()),
+            window_to: Set(to.naive_utc()),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([Column::ChatId, Column::Period, Column::WindowFrom, Column::WindowTo])
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_with_returning(Database::global().connection())
+        .await;
+
+        match res {
+            Ok(run) => Some(run),
+            // Nothing is returned on conflict
+            Err(DbErr::RecordNotFound(_)) => None,
+            Err(e) => {
+                error!("Can't claim scheduled run: {e}");
+                None
+            }
+        }
+    }
+
+    /// Claims the run again if it is not finished and was claimed before `claimed_before`, as its process is gone
+    pub async fn reclaim(
+        chat_id: i64,
+        period: &str,
+        from: DateTimeUtc,
+        to: DateTimeUtc,
+        claimed_before: NaiveDateTime,
+    ) -> Option<Model> {
+        let res = Entity::update_many()
+            .col_expr(Column::CreatedAt, Expr::value(Utc::now().naive_utc()))
+            .filter(Column::ChatId.eq(chat_id))
+            .filter(Column::Period.eq(period))
+            .filter(Column::WindowFrom.eq(from.naive_utc()))
+            .filter(Column::WindowTo.eq(to.naive_utc()))
+            .filter(Column::FinishedAt.is_null())
+            .filter(Column::CreatedAt.lt(claimed_before))
+            .exec_with_returning(Database::global().connection())
+            .await;
+
+        match res {
+            Ok(runs) => runs.into_iter().next(),
+            Err(e) => {
+                error!("Can't reclaim scheduled run: {e}");
+                None
+            }
+        }
+    }
+
+    /// Whether a run of an earlier window of the chat and period was ever claimed
+    pub async fn has_earlier(chat_id: i64, period: &str, from: DateTimeUtc) -> bool {
+        let res = Entity::find()
+            .filter(Column::ChatId.eq(chat_id))
+            .filter(Column::Period.eq(period))
+            .filter(Column::WindowFrom.lt(from.naive_utc()))
+            .one(Database::global().connection())
+            .await;
+
+        res.map(|run| run.is_some()).unwrap_or_else(|e| {
+            error!("Can't get scheduled runs from database: {e}");
+            false
+        })
+    }
+}
+
+impl Model {
+    pub async fn finish(&self) -> bool {
+        let mut model: ActiveModel = self.clone().into();
+
+        model.finished_at = Set(Some(Utc::now().naive_utc()));
+
+        model.update(Database::global().connection()).await.is_ok()
+    }
+}
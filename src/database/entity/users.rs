@@ -1,9 +1,14 @@
 use crate::database::entity::meme_likes::MemeLikeOperation;
-use crate::database::Database;
+use crate::database::{entity::RowStream, Database};
 use chrono::Utc;
+use chrono_tz::Tz;
 use sea_orm::entity::prelude::*;
 use sea_orm::sea_query::Alias;
-use sea_orm::{sea_query::OnConflict, FromQueryResult, JoinType, Order, QueryOrder, QuerySelect, Set};
+use sea_orm::{
+    sea_query::OnConflict, Condition, FromQueryResult, JoinType, Order, PaginatorTrait, QueryOrder, QuerySelect,
+    QueryTrait, Select, Set,
+};
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "users")]
@@ -15,6 +20,8 @@ pub struct Model {
     pub lastname: Option<String>,
     pub deleted_at: Option<DateTime>,
     pub created_at: Option<DateTime>,
+    /// The user gets the personal recap at the end of the year
+    pub recap: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -45,6 +52,63 @@ pub struct TopUser {
     pub count: i64,
 }
 
+#[derive(Debug, Clone)]
+pub struct RankedUser {
+    pub rank: usize,
+    pub user_id: i64,
+    pub count: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Leaderboard {
+    /// Sent memes
+    Memesenders,
+    /// Likes of the user's memes
+    Liked,
+    /// Dislikes of the user's memes
+    Disliked,
+    /// Given likes
+    Likers,
+    /// Given dislikes
+    Dislikers,
+    /// Likes of own memes
+    Selflikers,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UserStats {
+    pub memes: i64,
+    /// Likes and dislikes of the user's memes
+    pub likes: i64,
+    pub dislikes: i64,
+    pub likes_given: i64,
+    pub dislikes_given: i64,
+    pub selflikes: i64,
+    /// Place in the chat by likes of the memes
+    pub rank: Option<usize>,
+}
+
+#[derive(FromQueryResult, Serialize, Debug, Clone)]
+pub struct UserExport {
+    pub user_id: i64,
+    pub username: Option<String>,
+    pub firstname: String,
+    pub lastname: Option<String>,
+}
+
+/// Personal statistics of the user in all chats
+#[derive(Debug, Clone, Default)]
+pub struct UserRecap {
+    pub memes: i64,
+    /// The user who liked the user's memes most
+    pub fan: Option<TopUser>,
+    /// The author whose memes the user liked most
+    pub favourite: Option<TopUser>,
+    pub selflikes: i64,
+    /// Memes by months, January first
+    pub months: [i64; 12],
+}
+
 impl Entity {
     pub async fn add(model: ActiveModel) -> bool {
         Entity::insert(model)
@@ -58,6 +122,16 @@ impl Entity {
             .is_ok()
     }
 
+    /// Unlike `add` it doesn't restore deleted users
+    pub async fn add_if_absent(model: ActiveModel) -> bool {
+        Entity::insert(model)
+            .on_conflict(OnConflict::column(Column::UserId).do_nothing().to_owned())
+            .do_nothing()
+            .exec(Database::global().connection())
+            .await
+            .is_ok()
+    }
+
     pub async fn delete(user_id: i64) -> bool {
         Entity::update(ActiveModel {
             user_id: Set(user_id),
@@ -69,65 +143,335 @@ impl Entity {
         .is_ok()
     }
 
-    pub async fn top_selfliker(from: DateTimeUtc, to: DateTimeUtc) -> Option<TopUser> {
-        let res = super::memes::Entity::find()
+    pub async fn get_by_id(user_id: i64) -> Option<Model> {
+        let res = Self::find_by_id(user_id).one(Database::global().connection()).await;
+
+        res.unwrap_or_else(|e| {
+            error!("Can't get user from database: {e}");
+            None
+        })
+    }
+
+    pub async fn set_recap(user_id: i64, recap: bool) -> bool {
+        Entity::update(ActiveModel {
+            user_id: Set(user_id),
+            recap: Set(recap),
+            ..Default::default()
+        })
+        .exec(Database::global().connection())
+        .await
+        .is_ok()
+    }
+
+    pub async fn get_recap_subscribers() -> Vec<Model> {
+        let res = Self::find()
+            .filter(Column::Recap.eq(true))
+            .filter(Column::DeletedAt.is_null())
+            .all(Database::global().connection())
+            .await;
+
+        res.unwrap_or_else(|e| {
+            error!("Can't get users from database: {e}");
+            Vec::new()
+        })
+    }
+
+    /// Months are of the calendar in the timezone
+    pub async fn get_recap(user_id: i64, from: DateTimeUtc, to: DateTimeUtc, timezone: Tz) -> UserRecap {
+        let db = Database::global().connection();
+        let months = super::memes::Entity::find()
             .select_only()
-            .join(JoinType::InnerJoin, super::memes::Relation::MemeLikes.def())
+            .column_as(
+                Expr::cust_with_values(
+                    r#"CAST(EXTRACT(MONTH FROM "memes"."posted_at" AT TIME ZONE 'UTC' AT TIME ZONE $1) AS integer)"#,
+                    [timezone.name()],
+                ),
+                "month",
+            )
+            .column_as(super::memes::Column::Uuid.count(), "count")
+            .filter(super::memes::Column::UserId.eq(user_id))
+            .filter(super::memes::Column::RepostedAt.is_null())
+            .filter(super::memes::Column::PostedAt.gte(from))
+            .filter(super::memes::Column::PostedAt.lte(to))
+            .group_by(Expr::col(Alias::new("month")))
+            .into_tuple::<(i32, i64)>()
+            .all(db)
+            .await
+            .unwrap_or_else(|e| {
+                error!("Can't get memes by months from database: {e}");
+                Vec::new()
+            });
+
+        let mut recap = UserRecap::default();
+
+        for (month, count) in months {
+            if let Some(m) = recap.months.get_mut((month - 1) as usize) {
+                *m = count;
+            }
+            recap.memes += count;
+        }
+
+        let fan = Entity::find()
+            .select_only()
+            .join(JoinType::InnerJoin, Relation::MemeLikes.def())
+            .join(JoinType::InnerJoin, super::meme_likes::Relation::Memes.def())
+            .filter(super::memes::Column::UserId.eq(user_id))
+            .filter(super::memes::Column::RepostedAt.is_null())
+            .filter(super::meme_likes::Column::UserId.ne(user_id))
+            .filter(super::meme_likes::Column::Num.eq(MemeLikeOperation::Like.id()))
             .filter(super::meme_likes::Column::CreatedAt.gte(from))
             .filter(super::meme_likes::Column::CreatedAt.lte(to))
-            .filter(
-                Expr::col((super::memes::Memes::Table, super::memes::Column::UserId))
-                    .equals((super::meme_likes::MemeLikes::Table, super::meme_likes::Column::UserId)),
-            )
+            .group_by(Column::UserId)
+            .column(Column::UserId)
+            .column_as(super::meme_likes::Column::Num.count(), "count")
+            .order_by(Expr::col(Alias::new("count")), Order::Desc)
+            .into_model::<TopUser>()
+            .one(db)
+            .await;
+        let favourite = super::memes::Entity::find()
+            .select_only()
+            .join(JoinType::InnerJoin, super::memes::Relation::MemeLikes.def())
+            .filter(super::meme_likes::Column::UserId.eq(user_id))
+            .filter(super::memes::Column::UserId.ne(user_id))
+            .filter(super::memes::Column::RepostedAt.is_null())
             .filter(super::meme_likes::Column::Num.eq(MemeLikeOperation::Like.id()))
+            .filter(super::meme_likes::Column::CreatedAt.gte(from))
+            .filter(super::meme_likes::Column::CreatedAt.lte(to))
             .group_by(super::memes::Column::UserId)
             .column(super::memes::Column::UserId)
-            .column_as(super::meme_likes::Column::Num.sum(), "count")
-            .having(Expr::expr(super::meme_likes::Column::Num.sum()).gt(0))
+            .column_as(super::meme_likes::Column::Num.count(), "count")
             .order_by(Expr::col(Alias::new("count")), Order::Desc)
             .into_model::<TopUser>()
-            .one(Database::global().connection())
+            .one(db)
+            .await;
+        let selflikes = super::meme_likes::Entity::find()
+            .join(JoinType::InnerJoin, super::meme_likes::Relation::Memes.def())
+            .filter(super::memes::Column::UserId.eq(user_id))
+            .filter(super::memes::Column::RepostedAt.is_null())
+            .filter(super::meme_likes::Column::UserId.eq(user_id))
+            .filter(super::meme_likes::Column::Num.eq(MemeLikeOperation::Like.id()))
+            .filter(super::meme_likes::Column::CreatedAt.gte(from))
+            .filter(super::meme_likes::Column::CreatedAt.lte(to))
+            .count(db)
             .await;
 
-        res.unwrap_or_else(|e| {
-            error!("Can't get top selfliker from database: {e}");
+        recap.fan = fan.unwrap_or_else(|e| {
+            error!("Can't get fan of user from database: {e}");
             None
-        })
+        });
+        recap.favourite = favourite.unwrap_or_else(|e| {
+            error!("Can't get favourite author of user from database: {e}");
+            None
+        });
+        recap.selflikes = selflikes.unwrap_or_else(|e| {
+            error!("Can't get selflikes of user from database: {e}");
+            0
+        }) as i64;
+
+        recap
     }
 
-    pub async fn top_memesender(from: DateTimeUtc, to: DateTimeUtc) -> Option<TopUser> {
-        let res = Entity::find()
+    /// Users who posted memes to the chat or voted there in the period
+    pub async fn export(chat_id: i64, from: DateTimeUtc, to: DateTimeUtc) -> Result<RowStream<UserExport>, DbErr> {
+        let memesenders = super::memes::Entity::find()
             .select_only()
-            .join(JoinType::InnerJoin, Relation::Memes.def())
+            .column(super::memes::Column::UserId)
+            .filter(super::memes::Column::ChatId.eq(chat_id))
             .filter(super::memes::Column::PostedAt.gte(from))
             .filter(super::memes::Column::PostedAt.lte(to))
-            .group_by(Column::UserId)
-            .column(Column::UserId)
-            .column_as(super::memes::Column::Uuid.count(), "count")
-            .having(Expr::expr(super::memes::Column::Uuid.count()).gt(0))
-            .order_by(Expr::col(Alias::new("count")), Order::Desc)
+            .into_query();
+        let voters = super::meme_likes::Entity::find()
+            .select_only()
+            .column(super::meme_likes::Column::UserId)
+            .join(JoinType::InnerJoin, super::meme_likes::Relation::Memes.def())
+            .filter(super::memes::Column::ChatId.eq(chat_id))
+            .filter(super::meme_likes::Column::CreatedAt.gte(from))
+            .filter(super::meme_likes::Column::CreatedAt.lte(to))
+            .into_query();
+
+        Entity::find()
+            .select_only()
+            .columns([Column::UserId, Column::Username, Column::Firstname, Column::Lastname])
+            .filter(
+                Condition::any()
+                    .add(Column::UserId.in_subquery(memesenders))
+                    .add(Column::UserId.in_subquery(voters)),
+            )
+            .order_by_asc(Column::UserId)
+            .into_model::<UserExport>()
+            .stream(Database::global().connection())
+            .await
+    }
+
+    /// Leaders of the board, with ties it can be longer than `size`
+    pub async fn top(
+        chat_id: i64,
+        board: Leaderboard,
+        from: DateTimeUtc,
+        to: DateTimeUtc,
+        size: usize,
+    ) -> Vec<RankedUser> {
+        let mut users = Self::leaderboard(chat_id, board, from, to).await;
+
+        users.retain(|u| u.rank <= size);
+        users
+    }
+
+    pub async fn leaderboard(chat_id: i64, board: Leaderboard, from: DateTimeUtc, to: DateTimeUtc) -> Vec<RankedUser> {
+        let db = Database::global().connection();
+        let res = match board {
+            Leaderboard::Memesenders => Self::memesenders(chat_id, from, to).into_model().all(db).await,
+            Leaderboard::Liked => {
+                Self::authors(chat_id, from, to, MemeLikeOperation::Like)
+                    .into_model()
+                    .all(db)
+                    .await
+            }
+            Leaderboard::Disliked => {
+                Self::authors(chat_id, from, to, MemeLikeOperation::Dislike)
+                    .into_model()
+                    .all(db)
+                    .await
+            }
+            Leaderboard::Likers => {
+                Self::operationists(chat_id, from, to, MemeLikeOperation::Like)
+                    .into_model()
+                    .all(db)
+                    .await
+            }
+            Leaderboard::Dislikers => {
+                Self::operationists(chat_id, from, to, MemeLikeOperation::Dislike)
+                    .into_model()
+                    .all(db)
+                    .await
+            }
+            Leaderboard::Selflikers => Self::selflikers(chat_id, from, to).into_model().all(db).await,
+        };
+
+        Self::rank(res.unwrap_or_else(|e| {
+            error!("Can't get {board:?} leaderboard from database: {e}");
+            Vec::new()
+        }))
+    }
+
+    /// Users with the same count share a place: 1, 2, 2, 4
+    fn rank(mut users: Vec<TopUser>) -> Vec<RankedUser> {
+        users.sort_by(|a, b| b.count.cmp(&a.count).then(a.user_id.cmp(&b.user_id)));
+
+        let mut ranked: Vec<RankedUser> = Vec::with_capacity(users.len());
+
+        for (i, user) in users.into_iter().enumerate() {
+            let rank = match ranked.last() {
+                Some(prev) if prev.count == user.count => prev.rank,
+                _ => i + 1,
+            };
+
+            ranked.push(RankedUser {
+                rank,
+                user_id: user.user_id,
+                count: user.count,
+            });
+        }
+
+        ranked
+    }
+
+    /// Statistics of one user, the same as the top ones but scoped to the user
+    pub async fn get_stats(chat_id: i64, user_id: i64, from: DateTimeUtc, to: DateTimeUtc) -> UserStats {
+        let liked = Self::leaderboard(chat_id, Leaderboard::Liked, from, to)
+            .await
+            .into_iter()
+            .find(|u| u.user_id == user_id);
+
+        UserStats {
+            memes: Self::user_count(Self::memesenders(chat_id, from, to), user_id, Column::UserId).await,
+            likes: liked.as_ref().map(|u| u.count).unwrap_or_default(),
+            dislikes: Self::user_count(
+                Self::authors(chat_id, from, to, MemeLikeOperation::Dislike),
+                user_id,
+                super::memes::Column::UserId,
+            )
+            .await,
+            likes_given: Self::user_count(
+                Self::operationists(chat_id, from, to, MemeLikeOperation::Like),
+                user_id,
+                Column::UserId,
+            )
+            .await,
+            dislikes_given: Self::user_count(
+                Self::operationists(chat_id, from, to, MemeLikeOperation::Dislike),
+                user_id,
+                Column::UserId,
+            )
+            .await,
+            selflikes: Self::user_count(
+                Self::selflikers(chat_id, from, to),
+                user_id,
+                super::memes::Column::UserId,
+            )
+            .await,
+            rank: liked.map(|u| u.rank),
+        }
+    }
+
+    async fn user_count<E: EntityTrait>(query: Select<E>, user_id: i64, column: impl ColumnTrait) -> i64 {
+        let res = query
+            .filter(column.eq(user_id))
             .into_model::<TopUser>()
             .one(Database::global().connection())
             .await;
 
         res.unwrap_or_else(|e| {
-            error!("Can't get top memesender from database: {e}");
+            error!("Can't get user statistics from database: {e}");
             None
         })
+        .map(|u| u.count)
+        .unwrap_or_default()
     }
 
-    pub async fn top_liker(from: DateTimeUtc, to: DateTimeUtc) -> Option<TopUser> {
-        Self::top_operationist(from, to, MemeLikeOperation::Like).await
+    fn selflikers(chat_id: i64, from: DateTimeUtc, to: DateTimeUtc) -> Select<super::memes::Entity> {
+        super::memes::Entity::find()
+            .select_only()
+            .join(JoinType::InnerJoin, super::memes::Relation::MemeLikes.def())
+            .filter(super::memes::Column::ChatId.eq(chat_id))
+            .filter(super::memes::Column::RepostedAt.is_null())
+            .filter(super::meme_likes::Column::CreatedAt.gte(from))
+            .filter(super::meme_likes::Column::CreatedAt.lte(to))
+            .filter(
+                Expr::col((super::memes::Memes::Table, super::memes::Column::UserId))
+                    .equals((super::meme_likes::MemeLikes::Table, super::meme_likes::Column::UserId)),
+            )
+            .filter(super::meme_likes::Column::Num.eq(MemeLikeOperation::Like.id()))
+            .group_by(super::memes::Column::UserId)
+            .column(super::memes::Column::UserId)
+            .column_as(super::meme_likes::Column::Num.sum(), "count")
+            .having(Expr::expr(super::meme_likes::Column::Num.sum()).gt(0))
+            .order_by(Expr::col(Alias::new("count")), Order::Desc)
     }
 
-    pub async fn top_disliker(from: DateTimeUtc, to: DateTimeUtc) -> Option<TopUser> {
-        Self::top_operationist(from, to, MemeLikeOperation::Dislike).await
+    fn memesenders(chat_id: i64, from: DateTimeUtc, to: DateTimeUtc) -> Select<Entity> {
+        Entity::find()
+            .select_only()
+            .join(JoinType::InnerJoin, Relation::Memes.def())
+            .filter(super::memes::Column::ChatId.eq(chat_id))
+            .filter(super::memes::Column::RepostedAt.is_null())
+            .filter(super::memes::Column::PostedAt.gte(from))
+            .filter(super::memes::Column::PostedAt.lte(to))
+            .group_by(Column::UserId)
+            .column(Column::UserId)
+            .column_as(super::memes::Column::Uuid.count(), "count")
+            .having(Expr::expr(super::memes::Column::Uuid.count()).gt(0))
+            .order_by(Expr::col(Alias::new("count")), Order::Desc)
     }
 
-    async fn top_operationist(from: DateTimeUtc, to: DateTimeUtc, operation: MemeLikeOperation) -> Option<TopUser> {
-        let res = Entity::find()
+    /// Users who gave likes or dislikes
+    fn operationists(chat_id: i64, from: DateTimeUtc, to: DateTimeUtc, operation: MemeLikeOperation) -> Select<Entity> {
+        Entity::find()
             .select_only()
             .join(JoinType::InnerJoin, Relation::MemeLikes.def())
+            .join(JoinType::InnerJoin, super::meme_likes::Relation::Memes.def())
+            .filter(super::memes::Column::ChatId.eq(chat_id))
+            .filter(super::memes::Column::RepostedAt.is_null())
             .filter(super::meme_likes::Column::CreatedAt.gte(from))
             .filter(super::meme_likes::Column::CreatedAt.lte(to))
             .filter(super::meme_likes::Column::Num.eq(operation.id()))
@@ -136,13 +480,27 @@ impl Entity {
             .column_as(super::meme_likes::Column::Num.count(), "count")
             .having(Expr::expr(super::meme_likes::Column::Num.count()).gt(0))
             .order_by(Expr::col(Alias::new("count")), Order::Desc)
-            .into_model::<TopUser>()
-            .one(Database::global().connection())
-            .await;
+    }
 
-        res.unwrap_or_else(|e| {
-            error!("Can't get top operationist from database: {e}");
-            None
-        })
+    /// Users whose memes got likes or dislikes
+    fn authors(
+        chat_id: i64,
+        from: DateTimeUtc,
+        to: DateTimeUtc,
+        operation: MemeLikeOperation,
+    ) -> Select<super::memes::Entity> {
+        super::memes::Entity::find()
+            .select_only()
+            .join(JoinType::InnerJoin, super::memes::Relation::MemeLikes.def())
+            .filter(super::memes::Column::ChatId.eq(chat_id))
+            .filter(super::memes::Column::RepostedAt.is_null())
+            .filter(super::meme_likes::Column::CreatedAt.gte(from))
+            .filter(super::meme_likes::Column::CreatedAt.lte(to))
+            .filter(super::meme_likes::Column::Num.eq(operation.id()))
+            .group_by(super::memes::Column::UserId)
+            .column(super::memes::Column::UserId)
+            .column_as(super::meme_likes::Column::Num.count(), "count")
+            .having(Expr::expr(super::meme_likes::Column::Num.count()).gt(0))
+            .order_by(Expr::col(Alias::new("count")), Order::Desc)
     }
 }
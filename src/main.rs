@@ -3,8 +3,9 @@ extern crate dotenv;
 extern crate log;
 extern crate pretty_env_logger;
 
-use chrono::NaiveDateTime;
-use std::sync::Arc;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use std::{path::PathBuf, str::FromStr, sync::Arc, time::Duration};
 
 use clap::Parser;
 use dotenv::dotenv;
@@ -13,11 +14,14 @@ use teloxide::{
     dptree,
 };
 
-use crate::app::Application;
-use crate::bot::{statistics::Statistics, BotManager};
-use crate::database::Database;
+use crate::app::{calendar::Calendar, export::ExportFormat, Application, Thresholds};
+use crate::bot::{
+    statistics::{Output, Statistics},
+    BotManager,
+};
+use crate::database::{entity::prelude::Chats, Database};
 use crate::redis::RedisManager;
-use crate::scheduler::Scheduler;
+use crate::scheduler::{Schedule, Scheduler};
 use app::utils::Period;
 
 mod app;
@@ -41,18 +45,101 @@ enum Commands {
     #[command(long_flag = "start", about = "Starts the bot daemon")]
     Start,
     #[command(long_flag = "meme_of_week", short_flag = 'w', about = "Send meme of week to chats")]
-    MemeOfWeek,
+    MemeOfWeek {
+        #[arg(long, help = "Print messages instead of sending them")]
+        dry_run: bool,
+    },
     #[command(long_flag = "meme_of_month", short_flag = 'm', about = "Send meme of month to chats")]
-    MemeOfMonth,
+    MemeOfMonth {
+        #[arg(long, help = "Print messages instead of sending them")]
+        dry_run: bool,
+    },
     #[command(long_flag = "meme_of_year", short_flag = 'y', about = "Send meme of year to chats")]
-    MemeOfYear,
+    MemeOfYear {
+        #[arg(long, help = "Print messages instead of sending them")]
+        dry_run: bool,
+    },
     #[command(long_flag = "meme_of_custom", short_flag = 'c', about = "Send meme of custom period to chats")]
     MemeOfCustom {
         #[arg(required = true, help = "Set date and time start period")]
         from: NaiveDateTime,
         #[arg(required = true, help = "Set date and time end period")]
-        to: NaiveDateTime
+        to: NaiveDateTime,
+        #[arg(long, help = "Print messages instead of sending them")]
+        dry_run: bool,
+    },
+    #[command(long_flag = "replay", about = "Replay stored memes against similarity thresholds")]
+    Replay {
+        #[arg(long, help = "Replay only this chat")]
+        chat_id: Option<i64>,
+        #[arg(long, value_delimiter = ',', help = "Similar thresholds to compare, comma separated")]
+        similar: Vec<f64>,
+        #[arg(long, help = "Duplicate threshold, the configured one by default")]
+        duplicate: Option<f64>,
+    },
+    #[command(long_flag = "rehash", about = "Recompute hashes of stored memes")]
+    Rehash {
+        #[arg(long, help = "Rehash only this chat")]
+        chat_id: Option<i64>,
+        #[arg(long, default_value_t = 500, help = "Delay between memes in milliseconds")]
+        delay: u64,
+        #[arg(long, help = "Only hash memes, without saving anything")]
+        dry_run: bool,
+        #[arg(long, help = "Start from the first meme instead of the saved progress")]
+        restart: bool,
+    },
+    #[command(long_flag = "schedule", about = "Override statistics timezone and schedule of a chat, omitted ones are reset")]
+    Schedule {
+        #[arg(required = true, help = "Chat id")]
+        chat_id: i64,
+        #[arg(long, help = "Timezone, like Europe/Moscow")]
+        timezone: Option<String>,
+        #[arg(long, help = "Jobs as period=cron pairs separated by ';', empty cron disables a job")]
+        jobs: Option<String>,
+    },
+    #[command(long_flag = "export", about = "Export memes, users and votes of a chat to files")]
+    Export {
+        #[arg(required = true, help = "Chat id")]
+        chat_id: i64,
+        #[arg(long, help = "Set date and time start period, since the first meme by default")]
+        from: Option<NaiveDateTime>,
+        #[arg(long, help = "Set date and time end period, now by default")]
+        to: Option<NaiveDateTime>,
+        #[arg(long, default_value = "csv", help = "Format of the files: csv or jsonl")]
+        format: ExportFormat,
+        #[arg(long, default_value = ".", help = "Directory for memes, users and votes files")]
+        output: PathBuf,
     },
+    #[command(long_flag = "import", about = "Import photos of a Telegram Desktop chat export as memes")]
+    Import {
+        #[arg(required = true, help = "Chat id")]
+        chat_id: i64,
+        #[arg(required = true, help = "Path to result.json of the export")]
+        path: PathBuf,
+    },
+    #[command(long_flag = "thresholds", about = "Override similarity thresholds of a chat, omitted ones are reset")]
+    Thresholds {
+        #[arg(required = true, help = "Chat id")]
+        chat_id: i64,
+        #[arg(long, help = "Similar threshold in percents")]
+        similar: Option<i16>,
+        #[arg(long, help = "Duplicate threshold in percents")]
+        duplicate: Option<i16>,
+    },
+}
+
+impl Commands {
+    /// Dry runs don't send anything to chats
+    fn is_dry_run(&self) -> bool {
+        matches!(
+            self,
+            Commands::MemeOfWeek { dry_run: true }
+                | Commands::MemeOfMonth { dry_run: true }
+                | Commands::MemeOfYear { dry_run: true }
+                | Commands::MemeOfCustom { dry_run: true, .. }
+                | Commands::Rehash { dry_run: true, .. }
+        )
+    }
 }
 
 #[tokio::main]
@@ -67,35 +154,43 @@ async fn main() {
 
     let args = Cli::parse();
     let app = Arc::new(Application::new());
-    let scheduler = Scheduler::new();
+    let scheduler = Scheduler::new(&app.config.scheduler);
 
+    let calendar = Calendar::new(&app.config.calendar).expect("Can't load calendar");
     let db = Database::new(&app.config.db_url).await;
     db.migrate().await.expect("Can't migrate database");
     let redis = RedisManager::connect(&app.config.redis_url);
     let bot = BotManager::new(&app.config.bot);
 
+    app::calendar::INSTANCE.set(calendar).expect("Can't set Calendar");
+    app::locale::INSTANCE
+        .set(app.config.language)
+        .expect("Can't set Language");
     database::INSTANCE.set(db).expect("Can't set database");
     bot::INSTANCE.set(bot).expect("Can't set BotManager");
     redis::INSTANCE.set(redis).expect("Can't set RedisManager");
 
-    app.register_chat();
-    app.check_version();
+    app.register_chats().await;
+
+    if !args.command.is_dry_run() {
+        app.check_version().await;
+    }
 
     match args.command {
-        Commands::MemeOfWeek => {
-            let stats = Statistics::new();
+        Commands::MemeOfWeek { dry_run } => {
+            let stats = statistics(dry_run);
             stats.send(&Period::Week).await;
         }
-        Commands::MemeOfMonth => {
-            let stats = Statistics::new();
+        Commands::MemeOfMonth { dry_run } => {
+            let stats = statistics(dry_run);
             stats.send(&Period::Month).await;
         }
-        Commands::MemeOfYear => {
-            let stats = Statistics::new();
+        Commands::MemeOfYear { dry_run } => {
+            let stats = statistics(dry_run);
             stats.send(&Period::Year).await;
         }
-        Commands::MemeOfCustom { from, to } => {
-            let stats = Statistics::new();
+        Commands::MemeOfCustom { from, to, dry_run } => {
+            let stats = statistics(dry_run);
             stats
                 .send(&Period::Custom {
                     from: from.and_utc(),
@@ -103,6 +198,132 @@ async fn main() {
                 })
                 .await;
         }
+        Commands::Replay {
+            chat_id,
+            similar,
+            duplicate,
+        } => {
+            let default = app.config.similarity.thresholds();
+            let duplicate = duplicate.unwrap_or(default.duplicate);
+            let similar = if similar.is_empty() {
+                vec![default.similar]
+            } else {
+                similar
+            };
+            let loosest = Thresholds {
+                similar: similar.iter().copied().fold(f64::MAX, f64::min),
+                duplicate,
+            };
+            let chats = match chat_id {
+                Some(chat_id) => vec![chat_id],
+                None => Chats::get_active().await.iter().map(|c| c.chat_id).collect(),
+            };
+
+            for chat_id in chats {
+                let report = app.replay(chat_id, &loosest).await;
+
+                println!("Chat {chat_id}, {} memes:", report.memes);
+
+                for similar in &similar {
+                    let (duplicates, similar_hits) = report.hits(&Thresholds {
+                        similar: *similar,
+                        duplicate,
+                    });
+
+                    println!("  similar > {similar}%, duplicate >= {duplicate}%: {duplicates} duplicates, {similar_hits} similar");
+                }
+            }
+        }
+        Commands::Rehash {
+            chat_id,
+            delay,
+            dry_run,
+            restart,
+        } => {
+            let stats = app
+                .rehash(chat_id, Duration::from_millis(delay), dry_run, restart)
+                .await;
+
+            info!(
+                "Rehash is finished: {} memes rehashed, {} changed, {} imported ones skipped, {} failed",
+                stats.rehashed, stats.changed, stats.skipped, stats.failed
+            );
+
+            if !dry_run && stats.failed > 0 {
+                info!("Run rehash again to continue from the first failed meme");
+            }
+
+            if !dry_run {
+                RedisManager::global().bump_index_generation();
+                info!("Indexes of the bot will be rebuilt on next search");
+            }
+        }
+        Commands::Schedule {
+            chat_id,
+            timezone,
+            jobs,
+        } => {
+            if let Some(Err(e)) = timezone.as_deref().map(Tz::from_str) {
+                error!("Wrong timezone: {e}");
+                return;
+            }
+
+            if let Some(Err(e)) = jobs.as_deref().map(Schedule::from_str) {
+                error!("Wrong schedule: {e}");
+                return;
+            }
+
+            if Chats::set_schedule(chat_id, timezone, jobs).await {
+                info!("Schedule of chat {chat_id} is set, restart the bot to apply it");
+            } else {
+                error!("Can't set schedule of chat {chat_id}");
+            }
+        }
+        Commands::Thresholds {
+            chat_id,
+            similar,
+            duplicate,
+        } => {
+            if Chats::set_thresholds(chat_id, similar, duplicate).await {
+                info!("Thresholds of chat {chat_id} are set to {similar:?}/{duplicate:?}");
+            } else {
+                error!("Can't set thresholds of chat {chat_id}");
+            }
+        }
+        Commands::Export {
+            chat_id,
+            from,
+            to,
+            format,
+            output,
+        } => {
+            let dates = (
+                from.map(|from| from.and_utc()).unwrap_or(DateTime::UNIX_EPOCH),
+                to.map(|to| to.and_utc()).unwrap_or_else(Utc::now),
+            );
+
+            match app.export(chat_id, dates, format, &output).await {
+                Ok(stats) => info!(
+                    "Exported {} memes, {} users and {} votes to {}",
+                    stats.memes,
+                    stats.users,
+                    stats.votes,
+                    output.display()
+                ),
+                Err(e) => error!("Can't export chat {chat_id}: {e}"),
+            }
+        }
+        Commands::Import { chat_id, path } => match app.import(chat_id, &path).await {
+            Ok(stats) => {
+                info!(
+                    "Import is finished: {} memes imported, {} skipped, {} failed",
+                    stats.imported, stats.skipped, stats.failed
+                );
+                RedisManager::global().bump_index_generation();
+                info!("Indexes of the bot will be rebuilt on next search");
+            }
+            Err(e) => error!("Can't import {}: {e}", path.display()),
+        },
         Commands::Start => {
             info!("MemeBot version = {}", &app.config.app_version);
 
@@ -123,3 +344,11 @@ async fn main() {
         }
     };
 }
+
+fn statistics(dry_run: bool) -> Statistics {
+    if dry_run {
+        Statistics::with_output(Output::Stdout)
+    } else {
+        Statistics::new()
+    }
+}
@@ -1,6 +1,7 @@
 use once_cell::sync::OnceCell;
 use redis::{Client as RedisClient, Commands, Connection, RedisResult};
 use serde_json::json;
+use uuid::Uuid;
 
 pub static INSTANCE: OnceCell<RedisManager> = OnceCell::new();
 
@@ -47,6 +48,13 @@ impl RedisManager {
             .expect("Can't register chat")
     }
 
+    pub fn unregister_chat(&self, chat_id: i64) {
+        let _: () = self
+            .get_connection()
+            .del(format!("{chat_id}_registered"))
+            .unwrap_or_default();
+    }
+
     pub fn set_chat_admins(&self, chat_id: i64, admins_uids: &Vec<u64>) -> bool {
         self.get_connection()
             .set(format!("{chat_id}_admins"), json!(admins_uids).to_string())
@@ -73,6 +81,76 @@ impl RedisManager {
             .expect("Can't set app version")
     }
 
+    pub fn get_rehash_cursor(&self, key: &str) -> Option<String> {
+        self.get_connection()
+            .get(format!("rehash_cursor_{key}"))
+            .unwrap_or(None)
+    }
+
+    pub fn set_rehash_cursor(&self, key: &str, cursor: &str) {
+        let _: () = self
+            .get_connection()
+            .set(format!("rehash_cursor_{key}"), cursor)
+            .unwrap_or_default();
+    }
+
+    pub fn clear_rehash_cursor(&self, key: &str) {
+        let _: () = self
+            .get_connection()
+            .del(format!("rehash_cursor_{key}"))
+            .unwrap_or_default();
+    }
+
+    /// Stored messages of the kind which were sent to the chat since all of them were sent
+    pub fn get_used_messages(&self, chat_id: i64, key: &str) -> Vec<Uuid> {
+        let uuids: Vec<String> = self
+            .get_connection()
+            .smembers(format!("{chat_id}_used_{key}"))
+            .unwrap_or_default();
+
+        uuids.iter().filter_map(|uuid| uuid.parse().ok()).collect()
+    }
+
+    pub fn add_used_message(&self, chat_id: i64, key: &str, uuid: Uuid) {
+        let _: () = self
+            .get_connection()
+            .sadd(format!("{chat_id}_used_{key}"), uuid.to_string())
+            .unwrap_or_default();
+    }
+
+    pub fn clear_used_messages(&self, chat_id: i64, key: &str) {
+        let _: () = self
+            .get_connection()
+            .del(format!("{chat_id}_used_{key}"))
+            .unwrap_or_default();
+    }
+
+    /// Stored message of the kind which was sent to the chat the last
+    pub fn get_last_message(&self, chat_id: i64, key: &str) -> Option<Uuid> {
+        let uuid: Option<String> = self
+            .get_connection()
+            .get(format!("{chat_id}_last_{key}"))
+            .unwrap_or_default();
+
+        uuid.and_then(|uuid| uuid.parse().ok())
+    }
+
+    pub fn set_last_message(&self, chat_id: i64, key: &str, uuid: Uuid) {
+        let _: () = self
+            .get_connection()
+            .set(format!("{chat_id}_last_{key}"), uuid.to_string())
+            .unwrap_or_default();
+    }
+
+    /// Generation of meme indexes, it is bumped when hashes are changed outside of the bot
+    pub fn get_index_generation(&self) -> u64 {
+        self.get_connection().get("index_generation").unwrap_or(0)
+    }
+
+    pub fn bump_index_generation(&self) {
+        let _: u64 = self.get_connection().incr("index_generation", 1).unwrap_or_default();
+    }
+
     fn get_connection(&self) -> Connection {
         self.client.get_connection().expect("Can't get connection")
     }
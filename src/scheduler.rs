@@ -1,44 +1,158 @@
-use crate::app::utils::Period;
+use crate::app::{calendar::Calendar, utils::Period};
 use crate::bot::statistics::Statistics;
+use crate::database::entity::prelude::{Chats, ScheduledRuns};
 use anyhow::Result;
+use chrono::{Duration, Utc};
+use envconfig::Envconfig;
+use std::{str::FromStr, sync::Arc};
 use tokio_cron_scheduler::{Job, JobScheduler};
 
-pub struct Scheduler {}
+#[derive(Envconfig, Clone, Debug)]
+pub struct SchedulerConfig {
+    #[envconfig(
+        from = "SCHEDULE",
+        default = "week=00 05 16 * * *;month=00 05 17 * * *;year=00 05 18 * * *"
+    )]
+    pub schedule: Schedule,
+    /// Missed statistics of windows ended not earlier are sent on startup
+    #[envconfig(from = "CATCH_UP_HOURS", default = "48")]
+    pub catch_up_hours: i64,
+    /// Statistics which sending was started this long ago and never finished is sent again on startup
+    #[envconfig(from = "RECLAIM_AFTER_MINUTES", default = "30")]
+    pub reclaim_after_minutes: i64,
+}
+
+/// Statistics jobs as `period=cron` pairs separated by `;`.
+///
+/// Periods are `week`, `month` and `year`. Jobs fire every day by cron,
+/// statistics is sent only on the last working day of its period after its cutoff.
+#[derive(Clone, Debug, Default)]
+pub struct Schedule {
+    jobs: Vec<ScheduledJob>,
+}
+
+#[derive(Clone, Debug)]
+struct ScheduledJob {
+    period: Period,
+    /// Empty one disables the default job of the period
+    cron: String,
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut jobs = Vec::new();
+
+        for job in s.split(';').map(str::trim).filter(|j| !j.is_empty()) {
+            let (period, cron) = job
+                .split_once('=')
+                .ok_or_else(|| format!("Job \"{job}\" is not like period=cron"))?;
+            let period = match period.trim() {
+                "week" => Period::Week,
+                "month" => Period::Month,
+                "year" => Period::Year,
+                period => return Err(format!("Unknown period: {period}")),
+            };
+
+            jobs.push(ScheduledJob {
+                period,
+                cron: cron.trim().to_string(),
+            });
+        }
+
+        Ok(Self { jobs })
+    }
+}
+
+impl Schedule {
+    /// Jobs of `overrides` replace the jobs of the same periods
+    pub fn merge(&self, overrides: &Schedule) -> Schedule {
+        let mut jobs = self
+            .jobs
+            .iter()
+            .filter(|job| overrides.jobs.iter().all(|o| o.period != job.period))
+            .cloned()
+            .collect::<Vec<ScheduledJob>>();
+
+        jobs.extend(overrides.jobs.iter().cloned());
+
+        Schedule { jobs }
+    }
+
+    fn active_jobs(&self) -> impl Iterator<Item = &ScheduledJob> {
+        self.jobs.iter().filter(|job| !job.cron.is_empty())
+    }
+}
+
+pub struct Scheduler {
+    config: SchedulerConfig,
+}
 
 impl Scheduler {
-    pub fn new() -> Self {
-        Scheduler {}
+    pub fn new(config: &SchedulerConfig) -> Self {
+        Scheduler { config: config.clone() }
     }
 
+    /// Chats with their own timezone or schedule get their own jobs.
+    /// Changes of them are applied after restart.
     pub async fn handle(&self) -> Result<JobScheduler> {
+        self.catch_up().await;
+
         let mut scheduler = JobScheduler::new().await?;
 
-        scheduler
-            .add(Job::new_async("00 05 16 * * Fri", |_uuid, _l| {
-                Box::pin(async move {
-                    let stats = Statistics::new();
-                    stats.send(&Period::Week).await;
-                })
-            })?)
-            .await?;
-
-        scheduler
-            .add(Job::new_async("00 05 17 * * *", |_uuid, _l| {
-                Box::pin(async move {
-                    let stats = Statistics::new();
-                    stats.send(&Period::Month).await;
-                })
-            })?)
-            .await?;
-
-        scheduler
-            .add(Job::new_async("00 05 18 * * *", |_uuid, _l| {
-                Box::pin(async move {
-                    let stats = Statistics::new();
-                    stats.send(&Period::Year).await;
-                })
-            })?)
-            .await?;
+        for job in self.config.schedule.active_jobs() {
+            let period = job.period;
+
+            scheduler
+                .add(Job::new_async_tz(
+                    job.cron.as_str(),
+                    Calendar::global().timezone(),
+                    move |_uuid, _l| {
+                        Box::pin(async move {
+                            let chat_ids = Chats::get_active()
+                                .await
+                                .iter()
+                                .filter(|c| !c.has_own_schedule())
+                                .map(|c| c.chat_id)
+                                .collect::<Vec<i64>>();
+
+                            Statistics::new()
+                                .send_to_chats(&period, &chat_ids, Calendar::global())
+                                .await;
+                        })
+                    },
+                )?)
+                .await?;
+        }
+
+        for chat in Chats::get_active().await.iter().filter(|c| c.has_own_schedule()) {
+            let chat_id = chat.chat_id;
+            let calendar = Arc::new(chat.calendar());
+            let schedule = match chat.schedule() {
+                Some(schedule) => self.config.schedule.merge(&schedule),
+                None => self.config.schedule.clone(),
+            };
+
+            for job in schedule.active_jobs() {
+                let period = job.period;
+                let job_calendar = calendar.clone();
+                let chat_job = Job::new_async_tz(job.cron.as_str(), calendar.timezone(), move |_uuid, _l| {
+                    let calendar = job_calendar.clone();
+
+                    Box::pin(async move {
+                        Statistics::new().send_to_chats(&period, &[chat_id], &calendar).await;
+                    })
+                });
+
+                match chat_job {
+                    Ok(chat_job) => {
+                        scheduler.add(chat_job).await?;
+                    }
+                    Err(e) => error!("Can't schedule {period:?} statistics of chat {chat_id}: {e}"),
+                }
+            }
+        }
 
         scheduler.shutdown_on_ctrl_c();
 
@@ -52,4 +166,45 @@ impl Scheduler {
 
         Ok(scheduler)
     }
+
+    /// Sends statistics of recently ended windows which were missed while the bot was down.
+    ///
+    /// Only chats with earlier runs in the ledger are caught up, so the statistics sent
+    /// before the ledger was there is not sent again.
+    async fn catch_up(&self) {
+        let Some(horizon) = Duration::try_hours(self.config.catch_up_hours) else {
+            return;
+        };
+        let reclaim_after = Duration::try_minutes(self.config.reclaim_after_minutes);
+        let now = Utc::now();
+        let statistics = Statistics::new();
+
+        for chat in Chats::get_active().await {
+            let schedule = match chat.schedule() {
+                Some(schedule) => self.config.schedule.merge(&schedule),
+                None => self.config.schedule.clone(),
+            };
+
+            let calendar = chat.calendar();
+
+            for job in schedule.active_jobs() {
+                let dates = job.period.last_dates(&calendar, now);
+
+                if now - dates.1 > horizon {
+                    continue;
+                }
+
+                if !ScheduledRuns::has_earlier(chat.chat_id, job.period.name(), dates.0).await {
+                    continue;
+                }
+
+                if statistics
+                    .send_once(chat.chat_id, &job.period, dates, reclaim_after)
+                    .await
+                {
+                    info!("Caught up {:?} statistics of chat {}", job.period, chat.chat_id);
+                }
+            }
+        }
+    }
 }